@@ -5,17 +5,47 @@ use std::sync::Arc;
 
 // -------------------- CORE SERVICES -------------------- //
 
-struct UserService;
+trait UserRepository: Send + Sync {
+    fn find_name(&self, id: &str) -> Option<String>;
+}
+
+struct InMemoryUserRepository;
+#[service(provides = dyn UserRepository)]
+impl InMemoryUserRepository {
+    pub fn new() -> Self { Self {} }
+}
+
+impl UserRepository for InMemoryUserRepository {
+    fn find_name(&self, id: &str) -> Option<String> {
+        (id == "1").then(|| "admin".to_string())
+    }
+}
+
+struct UserService {
+    repository: Arc<dyn UserRepository>,
+    greeting: String,
+    collaborators: Collaborators,
+}
 #[service]
 impl UserService {
-    pub fn new() -> Self { Self {} }
+    pub fn new(
+        repository: Arc<dyn UserRepository>,
+        #[value("${app.greeting:Hello from UserService!}")] greeting: String,
+        collaborators: Collaborators,
+    ) -> Self {
+        Self { repository, greeting, collaborators }
+    }
 
+    #[cacheable("users", key = id)]
     pub async fn find_by_id(&self, id: &str) -> String {
-        format!("Found user with ID: {}", id)
+        match self.repository.find_name(id) {
+            Some(name) => format!("Found user {} with ID: {}", name, id),
+            None => format!("No user with ID: {}", id),
+        }
     }
 
     pub async fn get_greeting(&self) -> String {
-        "Hello from UserService!".to_string()
+        self.greeting.clone()
     }
 }
 
@@ -40,47 +70,130 @@ impl AuthService {
 
 // -------------------- ORDER ECOSYSTEM -------------------- //
 
-struct InventoryService;
+struct InventoryService {
+    collaborators: Collaborators,
+}
 #[service]
 impl InventoryService {
-    pub fn new() -> Self { Self {} }
+    pub fn new(collaborators: Collaborators) -> Self { Self { collaborators } }
 
     pub async fn check_stock(&self, item_id: u32) -> bool {
-        item_id % 2 == 0 // mock: even items in stock
+        item_id.is_multiple_of(2) // mock: even items in stock
     }
 
-    pub async fn reserve_item(&self, item_id: u32) -> String {
-        if self.check_stock(item_id).await {
-            format!("Item {} reserved", item_id)
+    // Out of stock is an answer, not a failure: only the warehouse being out of
+    // reach counts against the breaker, which opens once 5 of the latest 10 calls failed
+    #[circuit_breaker(name = "inventory", failure_rate = 50, wait = "30s", fallback = "reserve_fallback")]
+    pub async fn reserve_item(&self, item_id: u32) -> Result<String, String> {
+        if item_id >= 100 {
+            // mock: items from 100 on are in a warehouse that cannot be reached
+            Err(format!("Warehouse of item {} unreachable", item_id))
+        } else if self.check_stock(item_id).await {
+            Ok(format!("Item {} reserved", item_id))
         } else {
-            format!("Item {} out of stock", item_id)
+            Ok(format!("Item {} out of stock", item_id))
         }
     }
+
+    async fn reserve_fallback(&self, item_id: u32) -> Result<String, String> {
+        Ok(format!("Item {} will be reserved once the inventory is back", item_id))
+    }
+}
+
+pub struct OrderPlaced {
+    pub order_id: u32,
+    pub item_id: u32,
 }
 
 struct OrderService {
     inventory_service: Arc<InventoryService>,
     user_service: Arc<UserService>,
+    events: Arc<EventPublisher>,
+    collaborators: Collaborators,
 }
 #[service]
 impl OrderService {
     pub fn new(
         inventory_service: Arc<InventoryService>,
-        user_service: Arc<UserService>
+        user_service: Arc<UserService>,
+        events: Arc<EventPublisher>,
+        collaborators: Collaborators,
     ) -> Self {
-        Self { inventory_service, user_service }
+        Self { inventory_service, user_service, events, collaborators }
     }
 
     pub async fn get_order(&self, order_id: u32) -> String {
         format!("Order {} details for user {}", order_id, self.user_service.get_greeting().await)
     }
 
+    #[intercept(TimingInterceptor)]
     pub async fn place_order(&self, order_id: u32, item_id: u32) -> String {
-        let reservation = self.inventory_service.reserve_item(item_id).await;
+        let reservation = self.inventory_service.reserve_item(item_id).await.unwrap_or_else(|err| err);
+        if let Err(err) = self.events.publish(OrderPlaced { order_id, item_id }).await {
+            eprintln!("{}", err);
+        }
         format!("Placed order {}: {}", order_id, reservation)
     }
 }
 
+struct TimingInterceptor;
+#[service]
+impl TimingInterceptor {
+    pub fn new() -> Self { Self {} }
+}
+
+impl Interceptor for TimingInterceptor {
+    async fn intercept(&self, invocation: &Invocation, proceed: Proceed<'_>) -> ReturnValue {
+        let started = std::time::Instant::now();
+        let value = proceed.proceed().await;
+        println!(
+            "{}::{}({}) -> {:?} in {:.2?}",
+            invocation.component, invocation.method, invocation.args.join(", "), value, started.elapsed(),
+        );
+        value
+    }
+}
+
+struct CacheReport {
+    caches: Arc<CacheManager>,
+}
+#[service]
+impl CacheReport {
+    pub fn new(caches: Arc<CacheManager>) -> Self { Self { caches } }
+
+    #[scheduled(fixed_rate = "1m", initial_delay = "1m")]
+    async fn report(&self) {
+        for name in self.caches.cache_names() {
+            println!("Cache {}: {:?}", name, self.caches.stats(&name));
+        }
+    }
+}
+
+struct ReportService {
+    collaborators: Collaborators,
+}
+#[service]
+impl ReportService {
+    pub fn new(collaborators: Collaborators) -> Self { Self { collaborators } }
+
+    #[async_method(executor = "reports")]
+    pub async fn order_report(self: &Arc<Self>, order_id: u32) -> String {
+        tokio::time::sleep(std::time::Duration::from_secs(1)).await;
+        format!("Report for order {}", order_id)
+    }
+}
+
+struct NotificationService;
+#[service]
+impl NotificationService {
+    pub fn new() -> Self { Self {} }
+
+    #[event_listener(async)]
+    async fn on_order_placed(&self, event: &OrderPlaced) {
+        println!("Order {} placed for item {}", event.order_id, event.item_id);
+    }
+}
+
 // -------------------- CONTROLLERS -------------------- //
 
 struct ApiController {
@@ -121,18 +234,26 @@ impl AuthController {
 
 struct OrderController {
     order_service: Arc<OrderService>,
+    report_service: Arc<ReportService>,
 }
-#[controller]
+#[rest_controller("/order")]
 impl OrderController {
-    pub fn new(order_service: Arc<OrderService>) -> Self { Self { order_service } }
+    pub fn new(order_service: Arc<OrderService>, report_service: Arc<ReportService>) -> Self {
+        Self { order_service, report_service }
+    }
 
-    #[get("/order/{id}")]
+    #[get("/{id}")]
     async fn get_order(&self, Path(id): Path<u32>) -> String {
         self.order_service.get_order(id).await
     }
 
-    #[post("/order/{order_id}/item/{item_id}")]
+    #[post("/{order_id}/item/{item_id}")]
     async fn place_order(&self, Path((order_id, item_id)): Path<(u32, u32)>) -> String {
         self.order_service.place_order(order_id, item_id).await
     }
+
+    #[get("/{id}/report")]
+    async fn order_report(&self, Path(id): Path<u32>) -> String {
+        self.report_service.order_report(id).await.unwrap_or_else(|err| err.to_string())
+    }
 }
@@ -0,0 +1,307 @@
+use crate::{block_on, on_current_thread_runtime, ApplicationStarting, BuildError, Component, ContainerError, Environment, Instance, Scope, ServiceContainer};
+use std::any::TypeId;
+use std::sync::Arc;
+
+// Builds a container from the registered components, with some of them replaced
+// or left out, e.g. to test a controller against a fake service while the rest
+// of the graph is real. See `ServiceContainer::builder`.
+pub struct ContainerBuilder {
+    environment: Option<Environment>,
+    overrides: Vec<Override>,
+    excluded: Vec<TypeId>,
+    // Every component unless some are selected by module or tag
+    modules: Vec<String>,
+    tags: Vec<String>,
+    parent: Option<Arc<ServiceContainer>>,
+    // Published once the container exists, before its singletons are built
+    starting: Option<ApplicationStarting>,
+}
+
+// A ready-made instance standing in for every component of its type
+struct Override {
+    type_id: TypeId,
+    type_name: fn() -> &'static str,
+    instance: Instance,
+}
+
+impl ContainerBuilder {
+    pub(crate) fn new() -> Self {
+        Self {
+            environment: None,
+            overrides: Vec::new(),
+            excluded: Vec::new(),
+            modules: Vec::new(),
+            tags: Vec::new(),
+            parent: None,
+            starting: None,
+        }
+    }
+
+    // Defaults to the parent's environment, or `Environment::load`
+    pub fn environment(mut self, environment: Environment) -> Self {
+        self.environment = Some(environment);
+        self
+    }
+
+    pub fn property(mut self, key: &str, value: &str) -> Self {
+        let parent = self.parent.as_ref();
+        self.environment
+            .get_or_insert_with(|| parent.map_or_else(Environment::load, |parent| parent.environment().clone()))
+            .set(key, value);
+        self
+    }
+
+    // Only the components declared in `path` or its submodules, e.g. "myapp::billing",
+    // along with those selected by other calls to `module` and `tag`
+    pub fn module(mut self, path: &str) -> Self {
+        self.modules.push(path.to_string());
+        self
+    }
+
+    // Only the components tagged `tag` with #[service(tags("billing"))],
+    // along with those selected by other calls to `module` and `tag`
+    pub fn tag(mut self, tag: &str) -> Self {
+        self.tags.push(tag.to_string());
+        self
+    }
+
+    // Components of `parent` can be injected when no component of the new
+    // container provides the type; the parent never sees the child's components
+    pub fn parent(mut self, parent: &Arc<ServiceContainer>) -> Self {
+        self.parent = Some(parent.clone());
+        self
+    }
+
+    pub(crate) fn starting(mut self, event: ApplicationStarting) -> Self {
+        self.starting = Some(event);
+        self
+    }
+
+    // Inject `instance` wherever `T` is needed, instead of the components
+    // registered as `T`, which are not built
+    pub fn override_with<T: ?Sized + 'static + Send + Sync>(mut self, instance: Arc<T>) -> Self {
+        let type_id = TypeId::of::<T>();
+        self.overrides.retain(|o| o.type_id != type_id);
+        self.overrides.push(Override {
+            type_id,
+            type_name: std::any::type_name::<T>,
+            instance: Arc::new(instance),
+        });
+        self
+    }
+
+    // Leave out every component that can be injected as `T`
+    pub fn exclude<T: ?Sized + 'static>(mut self) -> Self {
+        self.excluded.push(TypeId::of::<T>());
+        self
+    }
+
+    pub fn build(self) -> Arc<ServiceContainer> {
+        self.try_build().unwrap_or_else(|err| panic!("{}", err))
+    }
+
+    // On a current-thread runtime, singletons are built by tasks that cannot run
+    // while this thread blocks, so use `try_build_async` there
+    pub fn try_build(self) -> Result<Arc<ServiceContainer>, ContainerError> {
+        if on_current_thread_runtime() {
+            return Err(ContainerError::BlockingBuild);
+        }
+        block_on(self.try_build_async()).unwrap_or(Err(ContainerError::BlockingBuild))
+    }
+
+    pub async fn try_build_async(self) -> Result<Arc<ServiceContainer>, ContainerError> {
+        let environment = match (self.environment, &self.parent) {
+            (Some(environment), _) => environment,
+            (None, Some(parent)) => parent.environment().clone(),
+            (None, None) => Environment::load(),
+        };
+        let replaced = |comp: &Component, type_id: TypeId| {
+            comp.type_id == type_id || comp.provides.iter().any(|p| p.type_id == type_id)
+        };
+        let selected = |comp: &Component| {
+            let in_module = |path: &str| {
+                comp.module_path.strip_prefix(path).is_some_and(|rest| rest.is_empty() || rest.starts_with("::"))
+            };
+            // Framework components such as the EventPublisher are part of every context
+            (self.modules.is_empty() && self.tags.is_empty())
+                || in_module(module_path!().split("::").next().unwrap())
+                || self.modules.iter().any(|path| in_module(path))
+                || self.tags.iter().any(|tag| comp.tags.contains(&tag.as_str()))
+        };
+
+        let mut components: Vec<&'static Component> = inventory::iter::<Component>()
+            .filter(|comp| selected(comp))
+            .filter(|comp| !self.excluded.iter().any(|&type_id| replaced(comp, type_id)))
+            .collect();
+
+        let mut overrides = Vec::new();
+        for o in self.overrides {
+            let originals: Vec<_> = components.iter().copied().filter(|comp| replaced(comp, o.type_id)).collect();
+            components.retain(|comp| !replaced(comp, o.type_id));
+            // Keep the name of the component it replaces, so qualifiers still match
+            let name = match originals[..] {
+                [comp] => comp.name,
+                _ => (o.type_name)(),
+            };
+            // and a concrete type is still injected as the traits it provides
+            let provides = originals.iter().find(|comp| comp.type_id == o.type_id).map_or(&[][..], |comp| comp.provides);
+
+            let comp = Arc::new(Component {
+                name,
+                type_id: o.type_id,
+                type_name: o.type_name,
+                module_path: module_path!(),
+                tags: &[],
+                scope: Scope::Singleton,
+                primary: false,
+                order: i32::MAX,
+                lazy: false,
+                conditions: &[],
+                provides,
+                dependencies: &[],
+                build: |_| Box::pin(async { Err(BuildError::from("overridden components are never built")) }),
+                post_construct: None,
+                pre_destroy: None,
+            });
+            overrides.push((comp, o.instance));
+        }
+
+        ServiceContainer::assemble(environment, components, overrides, self.parent, self.starting).await
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_support::component;
+    use crate::{Condition, Dependency, ProvidedType};
+
+    // The components below are only registered when the property is set, so
+    // the other tests building a container do not see them
+    const ENABLED: &[Condition] = &[Condition::OnProperty { name: "test.builder", having_value: None, match_if_missing: false }];
+
+    trait Repo: Send + Sync {
+        fn label(&self) -> &str;
+    }
+
+    struct MemRepo(&'static str);
+
+    impl Repo for MemRepo {
+        fn label(&self) -> &str {
+            self.0
+        }
+    }
+
+    struct Billing {
+        repo: Arc<dyn Repo>,
+    }
+
+    struct Shipping {
+        repo: Arc<dyn Repo>,
+    }
+
+    inventory::submit! {
+        Component {
+            module_path: "app::storage",
+            conditions: ENABLED,
+            provides: &[ProvidedType {
+                type_id: TypeId::of::<dyn Repo>(),
+                type_name: std::any::type_name::<dyn Repo>,
+                cast: |instance| {
+                    let concrete = instance.downcast_ref::<Arc<MemRepo>>().unwrap().clone();
+                    Arc::new(concrete as Arc<dyn Repo>)
+                },
+            }],
+            build: |_| Box::pin(async { Ok(Arc::new(Arc::new(MemRepo("memory"))) as Instance) }),
+            ..component::<MemRepo>("MemRepo", &[])
+        }
+    }
+
+    inventory::submit! {
+        Component {
+            module_path: "app::billing",
+            tags: &["core"],
+            conditions: ENABLED,
+            build: |container| Box::pin(async move {
+                let repo = container.get_named::<dyn Repo>("MemRepo");
+                Ok(Arc::new(Arc::new(Billing { repo })) as Instance)
+            }),
+            ..component::<Billing>("Billing", &[Dependency::of::<dyn Repo>().named("MemRepo")])
+        }
+    }
+
+    inventory::submit! {
+        Component {
+            module_path: "app::shipping",
+            conditions: ENABLED,
+            build: |container| Box::pin(async move {
+                let repo = container.get::<dyn Repo>();
+                Ok(Arc::new(Arc::new(Shipping { repo })) as Instance)
+            }),
+            ..component::<Shipping>("Shipping", &[Dependency::of::<dyn Repo>()])
+        }
+    }
+
+    fn builder() -> ContainerBuilder {
+        ServiceContainer::builder().environment(Environment::new()).property("test.builder", "true")
+    }
+
+    #[tokio::test]
+    async fn overriding_a_dependency_injects_the_override() {
+        let container = builder().override_with(Arc::new(MemRepo("fake"))).try_build_async().await.unwrap();
+
+        // Still injected as the trait it provides, under its name
+        assert_eq!(container.get::<Shipping>().repo.label(), "fake");
+        assert_eq!(container.get::<Billing>().repo.label(), "fake");
+        assert_eq!(container.get_named::<MemRepo>("MemRepo").label(), "fake");
+    }
+
+    #[tokio::test]
+    async fn overriding_a_trait_keeps_the_name_of_the_replaced_component() {
+        let fake: Arc<dyn Repo> = Arc::new(MemRepo("fake"));
+        let container = builder().override_with(fake).try_build_async().await.unwrap();
+
+        assert_eq!(container.get::<Billing>().repo.label(), "fake");
+        assert_eq!(container.get_named::<dyn Repo>("MemRepo").label(), "fake");
+        assert!(container.try_get::<MemRepo>().is_none());
+    }
+
+    #[tokio::test]
+    async fn excluding_a_dependency_fails_its_consumers() {
+        let result = builder().exclude::<dyn Repo>().try_build_async().await;
+        let Err(err) = result else {
+            panic!("expected the dependency to be missing");
+        };
+        assert!(matches!(err, ContainerError::MissingDependency { component: "Billing" | "Shipping", .. }), "{}", err);
+
+        let container = builder().exclude::<dyn Repo>().exclude::<Billing>().exclude::<Shipping>()
+            .try_build_async().await.unwrap();
+        assert!(container.try_get::<MemRepo>().is_none());
+        assert!(container.try_get::<Billing>().is_none());
+    }
+
+    #[tokio::test]
+    async fn modules_and_tags_select_components() {
+        let container = builder().module("app::storage").try_build_async().await.unwrap();
+        assert!(container.try_get::<MemRepo>().is_some());
+        assert!(container.try_get::<Billing>().is_none());
+
+        // Only whole path segments match
+        let container = builder().module("app::stor").module("app::storage::repos").try_build_async().await.unwrap();
+        assert!(container.try_get::<MemRepo>().is_none());
+
+        let container = builder().module("app::storage").tag("core").try_build_async().await.unwrap();
+        assert!(container.try_get::<Billing>().is_some());
+        assert!(container.try_get::<Shipping>().is_none());
+    }
+
+    #[tokio::test]
+    async fn children_inject_the_components_of_their_parent() {
+        let parent = builder().module("app::storage").try_build_async().await.unwrap();
+        let child = ServiceContainer::builder().parent(&parent).module("app::shipping").try_build_async().await.unwrap();
+
+        assert_eq!(child.get::<Shipping>().repo.label(), "memory");
+        assert!(Arc::ptr_eq(&child.get::<MemRepo>(), &parent.get::<MemRepo>()));
+        assert!(parent.try_get::<Shipping>().is_none());
+    }
+}
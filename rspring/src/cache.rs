@@ -0,0 +1,325 @@
+use crate::{Component, Environment, Instance};
+use std::any::Any;
+use std::collections::{BTreeMap, HashMap};
+use std::future::Future;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+
+// Hits and misses since the cache was created
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct CacheStats {
+    pub hits: u64,
+    pub misses: u64,
+    // Entries removed to stay under the max size; expired and evicted ones are not counted
+    pub evictions: u64,
+    pub size: usize,
+}
+
+// Injectable as `Arc<CacheManager>`; holds the caches used by #[cacheable],
+// #[cache_put] and #[cache_evict], created on first use and configured by
+// `cache.<name>.ttl` (no expiry by default) and `cache.<name>.max-size` (10000)
+pub struct CacheManager {
+    environment: Arc<Environment>,
+    caches: Mutex<HashMap<String, Arc<Cache>>>,
+}
+
+impl CacheManager {
+    pub const DEFAULT_MAX_SIZE: usize = 10_000;
+
+    pub fn cache(&self, name: &str) -> Arc<Cache> {
+        let mut caches = self.caches.lock().unwrap();
+        if let Some(cache) = caches.get(name) {
+            return cache.clone();
+        }
+        let ttl = match self.environment.duration(&format!("cache.{}.ttl", name), None) {
+            Ok(ttl) => Some(ttl),
+            Err(crate::PropertyError::Missing { .. }) => None,
+            Err(err) => {
+                eprintln!("[rspring] {}, cache {} does not expire", err, name);
+                None
+            }
+        };
+        let max_size = self.environment
+            .value(&format!("cache.{}.max-size", name), Some(&Self::DEFAULT_MAX_SIZE.to_string()))
+            .unwrap_or_else(|err| {
+                eprintln!("[rspring] {}, cache {} keeps up to {} entries", err, name, Self::DEFAULT_MAX_SIZE);
+                Self::DEFAULT_MAX_SIZE
+            });
+        let cache = Arc::new(Cache::new(name, ttl, max_size));
+        caches.insert(name.to_string(), cache.clone());
+        cache
+    }
+
+    // Sorted
+    pub fn cache_names(&self) -> Vec<String> {
+        let mut names: Vec<String> = self.caches.lock().unwrap().keys().cloned().collect();
+        names.sort();
+        names
+    }
+
+    pub fn stats(&self, name: &str) -> Option<CacheStats> {
+        self.caches.lock().unwrap().get(name).map(|cache| cache.stats())
+    }
+}
+
+// An in-memory cache keyed by strings, holding values of any Clone type. The
+// least recently used entry is dropped when it grows past its max size.
+pub struct Cache {
+    name: String,
+    ttl: Option<Duration>,
+    max_size: usize,
+    entries: Mutex<Entries>,
+    // One lock per key being loaded, so concurrent misses compute the value once
+    loading: Mutex<HashMap<String, Arc<tokio::sync::Mutex<()>>>>,
+    hits: AtomicU64,
+    misses: AtomicU64,
+    evictions: AtomicU64,
+}
+
+#[derive(Default)]
+struct Entries {
+    by_key: HashMap<String, Entry>,
+    // Keys by last use, oldest first
+    by_use: BTreeMap<u64, String>,
+    clock: u64,
+}
+
+struct Entry {
+    value: Arc<dyn Any + Send + Sync>,
+    expires: Option<Instant>,
+    used: u64,
+}
+
+impl Cache {
+    pub fn new(name: &str, ttl: Option<Duration>, max_size: usize) -> Self {
+        Self {
+            name: name.to_string(),
+            ttl,
+            max_size,
+            entries: Mutex::new(Entries::default()),
+            loading: Mutex::new(HashMap::new()),
+            hits: AtomicU64::new(0),
+            misses: AtomicU64::new(0),
+            evictions: AtomicU64::new(0),
+        }
+    }
+
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    // A copy of the value stored under `key`, counted as a hit or a miss. A value
+    // of another type than `T` counts as a miss.
+    pub fn get<T: Any + Clone>(&self, key: &str) -> Option<T> {
+        let value = self.lookup(key);
+        let counter = if value.is_some() { &self.hits } else { &self.misses };
+        counter.fetch_add(1, Ordering::Relaxed);
+        value
+    }
+
+    pub fn put<T: Any + Send + Sync>(&self, key: &str, value: T) {
+        let mut entries = self.entries.lock().unwrap();
+        entries.clock += 1;
+        let used = entries.clock;
+        let entry = Entry {
+            value: Arc::new(value),
+            expires: self.ttl.map(|ttl| Instant::now() + ttl),
+            used,
+        };
+        if let Some(old) = entries.by_key.insert(key.to_string(), entry) {
+            entries.by_use.remove(&old.used);
+        }
+        entries.by_use.insert(used, key.to_string());
+
+        while entries.by_key.len() > self.max_size {
+            let Some((_, oldest)) = entries.by_use.pop_first() else {
+                break;
+            };
+            entries.by_key.remove(&oldest);
+            self.evictions.fetch_add(1, Ordering::Relaxed);
+        }
+    }
+
+    pub fn evict(&self, key: &str) {
+        let mut entries = self.entries.lock().unwrap();
+        if let Some(old) = entries.by_key.remove(key) {
+            entries.by_use.remove(&old.used);
+        }
+    }
+
+    pub fn clear(&self) {
+        let mut entries = self.entries.lock().unwrap();
+        entries.by_key.clear();
+        entries.by_use.clear();
+    }
+
+    pub fn stats(&self) -> CacheStats {
+        CacheStats {
+            hits: self.hits.load(Ordering::Relaxed),
+            misses: self.misses.load(Ordering::Relaxed),
+            evictions: self.evictions.load(Ordering::Relaxed),
+            size: self.entries.lock().unwrap().by_key.len(),
+        }
+    }
+
+    // The cached value, or the output of `load`, stored for next time. While a
+    // key is loading, other callers asking for it wait and get the stored value.
+    pub async fn get_or_load<T, F>(&self, key: String, load: F) -> T
+    where
+        T: Any + Clone + Send + Sync,
+        F: Future<Output = T>,
+    {
+        let loaded = self.get_or_try_load(key, async { Ok::<T, std::convert::Infallible>(load.await) }).await;
+        match loaded {
+            Ok(value) => value,
+        }
+    }
+
+    // Like `get_or_load`, but only `Ok` values are stored
+    pub async fn get_or_try_load<T, E, F>(&self, key: String, load: F) -> Result<T, E>
+    where
+        T: Any + Clone + Send + Sync,
+        F: Future<Output = Result<T, E>>,
+    {
+        if let Some(value) = self.lookup(&key) {
+            self.hits.fetch_add(1, Ordering::Relaxed);
+            return Ok(value);
+        }
+
+        let lock = self.loading.lock().unwrap().entry(key.clone()).or_default().clone();
+        let loading = Loading { cache: self, key: &key, lock };
+        let _loading = loading.lock.lock().await;
+        // Loaded by the caller we waited for
+        if let Some(value) = self.lookup(&key) {
+            self.hits.fetch_add(1, Ordering::Relaxed);
+            return Ok(value);
+        }
+
+        self.misses.fetch_add(1, Ordering::Relaxed);
+        let loaded = load.await;
+        if let Ok(value) = &loaded {
+            self.put(&key, value.clone());
+        }
+        loaded
+    }
+
+    // Marks the entry as used, dropping it if it expired
+    fn lookup<T: Any + Clone>(&self, key: &str) -> Option<T> {
+        let mut entries = self.entries.lock().unwrap();
+        let entries = &mut *entries;
+        let entry = entries.by_key.get_mut(key)?;
+        if entry.expires.is_some_and(|expires| expires <= Instant::now()) {
+            entries.by_use.remove(&entry.used);
+            entries.by_key.remove(key);
+            return None;
+        }
+
+        entries.clock += 1;
+        entries.by_use.remove(&entry.used);
+        entry.used = entries.clock;
+        entries.by_use.insert(entry.used, key.to_string());
+        entry.value.downcast_ref::<T>().cloned()
+    }
+}
+
+// A caller of `get_or_try_load` loading or waiting for a key. The last one to
+// leave, even by having its future dropped, removes the key's lock.
+struct Loading<'a> {
+    cache: &'a Cache,
+    key: &'a str,
+    lock: Arc<tokio::sync::Mutex<()>>,
+}
+
+impl Drop for Loading<'_> {
+    fn drop(&mut self) {
+        let mut loading = self.cache.loading.lock().unwrap();
+        // Held by the map and by this caller only
+        let last = Arc::strong_count(&self.lock) == 2;
+        if last && loading.get(self.key).is_some_and(|lock| Arc::ptr_eq(lock, &self.lock)) {
+            loading.remove(self.key);
+        }
+    }
+}
+
+inventory::submit! {
+    Component::framework::<CacheManager>("CacheManager", |container| {
+        let manager = CacheManager {
+            environment: container.environment.clone(),
+            caches: Mutex::new(HashMap::new()),
+        };
+        Box::pin(async move { Ok(Arc::new(Arc::new(manager)) as Instance) })
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::atomic::AtomicUsize;
+
+    #[test]
+    fn evicts_the_least_recently_used_entry() {
+        let cache = Cache::new("users", None, 2);
+        cache.put("a", 1);
+        cache.put("b", 2);
+        assert_eq!(cache.get::<i32>("a"), Some(1));
+        cache.put("c", 3);
+
+        assert_eq!(cache.get::<i32>("b"), None);
+        assert_eq!(cache.get::<i32>("a"), Some(1));
+        assert_eq!(cache.get::<i32>("c"), Some(3));
+        assert_eq!(cache.stats(), CacheStats { hits: 3, misses: 1, evictions: 1, size: 2 });
+    }
+
+    #[test]
+    fn entries_expire_after_the_ttl() {
+        let cache = Cache::new("users", Some(Duration::from_millis(20)), 10);
+        cache.put("a", 1);
+        assert_eq!(cache.get::<i32>("a"), Some(1));
+
+        std::thread::sleep(Duration::from_millis(30));
+        assert_eq!(cache.get::<i32>("a"), None);
+        assert_eq!(cache.stats().size, 0);
+        assert_eq!(cache.stats().evictions, 0);
+    }
+
+    #[tokio::test]
+    async fn concurrent_misses_load_once() {
+        let cache = Cache::new("users", None, 10);
+        let loads = AtomicUsize::new(0);
+        let load = || async {
+            loads.fetch_add(1, Ordering::Relaxed);
+            tokio::time::sleep(Duration::from_millis(10)).await;
+            "admin".to_string()
+        };
+
+        let values = tokio::join!(
+            cache.get_or_load("1".to_string(), load()),
+            cache.get_or_load("1".to_string(), load()),
+        );
+        assert_eq!(values, ("admin".to_string(), "admin".to_string()));
+        assert_eq!(loads.load(Ordering::Relaxed), 1);
+        assert_eq!(cache.stats(), CacheStats { hits: 1, misses: 1, evictions: 0, size: 1 });
+        assert!(cache.loading.lock().unwrap().is_empty());
+    }
+
+    #[tokio::test]
+    async fn failed_loads_are_not_stored() {
+        let cache = Cache::new("users", None, 10);
+        let failed = cache.get_or_try_load::<i32, _, _>("1".to_string(), async { Err("unavailable") }).await;
+        assert_eq!(failed, Err("unavailable"));
+        assert_eq!(cache.get_or_try_load::<_, &str, _>("1".to_string(), async { Ok(1) }).await, Ok(1));
+        assert_eq!(cache.get::<i32>("1"), Some(1));
+    }
+
+    #[tokio::test]
+    async fn a_dropped_load_releases_the_key() {
+        let cache = Cache::new("users", None, 10);
+        let pending = cache.get_or_load("1".to_string(), std::future::pending::<i32>());
+        let timed_out = tokio::time::timeout(Duration::from_millis(10), pending).await;
+        assert!(timed_out.is_err());
+        assert!(cache.loading.lock().unwrap().is_empty());
+
+        assert_eq!(cache.get_or_load("1".to_string(), async { 1 }).await, 1);
+    }
+}
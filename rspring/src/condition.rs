@@ -0,0 +1,213 @@
+use crate::{Component, Dependency, Environment};
+
+// Decides whether a component is registered at all, see #[conditional_on_property],
+// #[profile], #[conditional_on_bean] and #[conditional_on_missing_bean]
+pub enum Condition {
+    // Without `having_value`, any value but "false" matches
+    OnProperty {
+        name: &'static str,
+        having_value: Option<&'static str>,
+        match_if_missing: bool,
+    },
+    // Any of the profiles is active; "!dev" matches when "dev" is not
+    Profile(&'static [&'static str]),
+    OnBean(Dependency),
+    OnMissingBean(Dependency),
+}
+
+// Why a conditional component was included or skipped
+#[derive(Debug, Clone)]
+pub struct ConditionOutcome {
+    pub component: &'static str,
+    pub included: bool,
+    pub reason: String,
+}
+
+impl Condition {
+    // `Ok` with what matched, or `Err` with what did not
+    fn evaluate(&self, comp: &Component, env: &Environment, present: &[&Component]) -> Result<String, String> {
+        match self {
+            Condition::OnProperty { name, having_value, match_if_missing } => match (env.get(name), having_value) {
+                (None, _) if *match_if_missing => Ok(format!("property {} is not set", name)),
+                (None, _) => Err(format!("property {} is not set", name)),
+                (Some(value), Some(expected)) if value == *expected => Ok(format!("property {}={}", name, value)),
+                (Some(value), Some(expected)) => {
+                    Err(format!("property {} is \"{}\", expected \"{}\"", name, value, expected))
+                }
+                (Some(value), None) if value != "false" => Ok(format!("property {}={}", name, value)),
+                (Some(_), None) => Err(format!("property {} is false", name)),
+            },
+            Condition::Profile(profiles) => {
+                let active = env.active_profiles();
+                let matched = profiles.iter().find(|profile| match profile.strip_prefix('!') {
+                    Some(profile) => !active.iter().any(|p| p == profile),
+                    None => active.iter().any(|p| p == *profile),
+                });
+                match matched {
+                    Some(profile) => Ok(format!("profile {} matches", profile)),
+                    None => Err(format!(
+                        "profile {} does not match active profiles [{}]",
+                        profiles.join(", "),
+                        active.join(", ")
+                    )),
+                }
+            }
+            Condition::OnBean(dep) => match providers(comp, dep, present).as_slice() {
+                [] => Err(format!("no {} is registered", (dep.type_name)())),
+                found => Ok(format!("{} is provided by {}", (dep.type_name)(), found.join(", "))),
+            },
+            Condition::OnMissingBean(dep) => match providers(comp, dep, present).as_slice() {
+                [] => Ok(format!("no {} is registered", (dep.type_name)())),
+                found => Err(format!("{} is already provided by {}", (dep.type_name)(), found.join(", "))),
+            },
+        }
+    }
+
+    fn is_bean_condition(&self) -> bool {
+        matches!(self, Condition::OnBean(_) | Condition::OnMissingBean(_))
+    }
+}
+
+// Names of the other components that can be injected as the dependency's type
+fn providers(comp: &Component, dep: &Dependency, present: &[&Component]) -> Vec<&'static str> {
+    present.iter()
+        .filter(|other| other.type_id != comp.type_id)
+        .filter(|other| other.type_id == dep.type_id || other.provides.iter().any(|p| p.type_id == dep.type_id))
+        .map(|other| other.name)
+        .collect()
+}
+
+// Keep the components whose conditions hold. Property and profile conditions are
+// checked first; bean conditions then see the `inherited` components of parent
+// contexts, every unconditional component and the conditional ones accepted before
+// them. Components using #[conditional_on_bean] only go first, then the fallbacks
+// using #[conditional_on_missing_bean], each in name order, so that a fallback
+// sees every conditional component that may provide the bean, as in Spring.
+pub(crate) fn evaluate(
+    components: Vec<&'static Component>,
+    inherited: &[&'static Component],
+    env: &Environment,
+) -> (Vec<&'static Component>, Vec<ConditionOutcome>) {
+    let mut report = Vec::new();
+    let mut outcome = |comp: &'static Component, result: Result<Vec<String>, String>| {
+        let included = result.is_ok();
+        let reason = result.map(|reasons| reasons.join(", ")).unwrap_or_else(|reason| reason);
+        report.push(ConditionOutcome { component: comp.name, included, reason });
+        included
+    };
+
+    let mut present = Vec::new();
+    let mut bean_conditional = Vec::new();
+    for comp in components {
+        if comp.conditions.is_empty() {
+            present.push(comp);
+            continue;
+        }
+
+        let matched: Result<Vec<String>, String> = comp.conditions.iter()
+            .filter(|condition| !condition.is_bean_condition())
+            .map(|condition| condition.evaluate(comp, env, &[]))
+            .collect();
+        match matched {
+            Ok(_) if comp.conditions.iter().any(Condition::is_bean_condition) => bean_conditional.push(comp),
+            Ok(reasons) => {
+                outcome(comp, Ok(reasons));
+                present.push(comp);
+            }
+            Err(reason) => {
+                outcome(comp, Err(reason));
+            }
+        }
+    }
+
+    bean_conditional.sort_by_key(|comp| {
+        let fallback = comp.conditions.iter().any(|condition| matches!(condition, Condition::OnMissingBean(_)));
+        (fallback, comp.name)
+    });
+    for comp in bean_conditional {
+        // Inherited components shadowed by one of this context are not counted twice
+        let visible: Vec<_> = inherited.iter()
+            .filter(|comp| !present.iter().any(|p| p.type_id == comp.type_id))
+            .chain(&present)
+            .copied()
+            .collect();
+        let matched = comp.conditions.iter()
+            .map(|condition| condition.evaluate(comp, env, &visible))
+            .collect();
+        if outcome(comp, matched) {
+            present.push(comp);
+        }
+    }
+
+    (present, report)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{test_support, Instance, ProvidedType};
+    use std::any::TypeId;
+
+    trait CacheStore: Send + Sync {}
+    struct RedisClient;
+    struct RedisCache;
+    struct InMemoryCache;
+    impl CacheStore for RedisCache {}
+    impl CacheStore for InMemoryCache {}
+
+    const fn component<T: 'static>(
+        name: &'static str,
+        conditions: &'static [Condition],
+        provides: &'static [ProvidedType],
+    ) -> Component {
+        Component { conditions, provides, ..test_support::component::<T>(name, &[]) }
+    }
+
+    // Named so that the fallback sorts before the component that may provide the bean
+    static REDIS_CLIENT: Component = component::<RedisClient>("RedisClient", &[], &[]);
+    static REDIS_CACHE: Component = component::<RedisCache>(
+        "RedisCache",
+        &[Condition::OnBean(Dependency::of::<RedisClient>())],
+        &[ProvidedType {
+            type_id: TypeId::of::<dyn CacheStore>(),
+            type_name: std::any::type_name::<dyn CacheStore>,
+            cast: |instance: Instance| instance,
+        }],
+    );
+    static IN_MEMORY_CACHE: Component = component::<InMemoryCache>(
+        "InMemoryCache",
+        &[Condition::OnMissingBean(Dependency::of::<dyn CacheStore>())],
+        &[ProvidedType {
+            type_id: TypeId::of::<dyn CacheStore>(),
+            type_name: std::any::type_name::<dyn CacheStore>,
+            cast: |instance: Instance| instance,
+        }],
+    );
+
+    fn included(components: Vec<&'static Component>) -> Vec<&'static str> {
+        let (present, _) = evaluate(components, &[], &Environment::new());
+        let mut names: Vec<_> = present.iter().map(|comp| comp.name).collect();
+        names.sort();
+        names
+    }
+
+    #[test]
+    fn missing_bean_fallbacks_see_conditional_providers() {
+        let components = vec![&IN_MEMORY_CACHE, &REDIS_CACHE, &REDIS_CLIENT];
+        assert_eq!(included(components), ["RedisCache", "RedisClient"]);
+    }
+
+    #[test]
+    fn missing_bean_fallbacks_apply_when_no_provider_matches() {
+        let components = vec![&IN_MEMORY_CACHE, &REDIS_CACHE];
+        assert_eq!(included(components), ["InMemoryCache"]);
+    }
+
+    #[test]
+    fn report_explains_each_bean_condition() {
+        let (_, report) = evaluate(vec![&IN_MEMORY_CACHE, &REDIS_CACHE, &REDIS_CLIENT], &[], &Environment::new());
+        let skipped = report.iter().find(|outcome| outcome.component == "InMemoryCache").unwrap();
+        assert!(!skipped.included);
+        assert!(skipped.reason.contains("RedisCache"), "{}", skipped.reason);
+    }
+}
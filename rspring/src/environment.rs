@@ -0,0 +1,235 @@
+use std::collections::HashMap;
+use std::fmt;
+use std::fs;
+use std::path::Path;
+use std::str::FromStr;
+use std::time::Duration;
+
+// Application properties, looked up in this order:
+//   1. values set in code, e.g. `Application::property`
+//   2. environment variables, `server.port` is read from `SERVER_PORT`
+//   3. the `application.properties` file in the working directory
+// The last two are only read by `load`.
+#[derive(Debug, Default, Clone)]
+pub struct Environment {
+    overrides: HashMap<String, String>,
+    variables: HashMap<String, String>,
+    file: HashMap<String, String>,
+}
+
+impl Environment {
+    pub const PROPERTIES_FILE: &'static str = "application.properties";
+
+    // An environment with only the values set in code, the same in every process
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    // Reads the environment variables, and `application.properties` if it exists
+    pub fn load() -> Self {
+        let mut env = Self::new();
+        env.variables = std::env::vars_os()
+            .filter_map(|(name, value)| Some((name.into_string().ok()?, value.into_string().ok()?)))
+            .collect();
+        if Path::new(Self::PROPERTIES_FILE).exists() {
+            match fs::read_to_string(Self::PROPERTIES_FILE) {
+                Ok(contents) => env.file = parse_properties(&contents),
+                Err(err) => eprintln!("[rspring] Could not read {}: {}", Self::PROPERTIES_FILE, err),
+            }
+        }
+        env
+    }
+
+    pub fn set(&mut self, key: &str, value: &str) {
+        self.overrides.insert(key.to_string(), value.to_string());
+    }
+
+    pub fn get(&self, key: &str) -> Option<String> {
+        if let Some(value) = self.overrides.get(key) {
+            return Some(value.clone());
+        }
+        if let Some(value) = self.variables.get(&env_var_name(key)) {
+            return Some(value.clone());
+        }
+        self.file.get(key).cloned()
+    }
+
+    pub fn get_or(&self, key: &str, default: &str) -> String {
+        self.get(key).unwrap_or_else(|| default.to_string())
+    }
+
+    // Comma separated `rspring.profiles.active`
+    pub fn active_profiles(&self) -> Vec<String> {
+        self.get("rspring.profiles.active")
+            .map(|profiles| {
+                profiles.split(',')
+                    .map(|profile| profile.trim().to_string())
+                    .filter(|profile| !profile.is_empty())
+                    .collect()
+            })
+            .unwrap_or_default()
+    }
+
+    // `true`/`false` properties, anything else counts as unset
+    pub fn get_bool(&self, key: &str) -> Option<bool> {
+        self.get(key).and_then(|value| value.trim().parse().ok())
+    }
+
+    // The property parsed as `T`, or `default` when it is not set; injected for #[value("${key:default}")]
+    pub fn value<T: FromStr>(&self, key: &str, default: Option<&str>) -> Result<T, PropertyError>
+    where
+        T::Err: fmt::Display,
+    {
+        let value = self.get(key)
+            .or_else(|| default.map(str::to_string))
+            .ok_or_else(|| PropertyError::Missing { key: key.to_string() })?;
+        value.trim().parse().map_err(|err: T::Err| PropertyError::Invalid {
+            key: key.to_string(),
+            value: value.clone(),
+            expected: std::any::type_name::<T>(),
+            reason: err.to_string(),
+        })
+    }
+
+    // A duration such as "250ms", "30s", "5m" or "1h", or `default` when it is not set
+    pub fn duration(&self, key: &str, default: Option<&str>) -> Result<Duration, PropertyError> {
+        let value = self.get(key)
+            .or_else(|| default.map(str::to_string))
+            .ok_or_else(|| PropertyError::Missing { key: key.to_string() })?;
+        parse_duration(&value).map_err(|reason| PropertyError::Invalid {
+            key: key.to_string(),
+            value: value.clone(),
+            expected: "duration",
+            reason,
+        })
+    }
+}
+
+// A number followed by ms, s, m, h or d
+pub fn parse_duration(value: &str) -> Result<Duration, String> {
+    let value = value.trim();
+    let split = value.find(|c: char| !c.is_ascii_digit()).unwrap_or(value.len());
+    let (amount, unit) = value.split_at(split);
+    let amount: u64 = amount.parse().map_err(|_| "expected a number followed by ms, s, m, h or d".to_string())?;
+    match unit.trim() {
+        "ms" => Ok(Duration::from_millis(amount)),
+        "s" => Ok(Duration::from_secs(amount)),
+        "m" => Ok(Duration::from_secs(amount * 60)),
+        "h" => Ok(Duration::from_secs(amount * 60 * 60)),
+        "d" => Ok(Duration::from_secs(amount * 24 * 60 * 60)),
+        unit => Err(format!("unknown unit \"{}\", expected ms, s, m, h or d", unit)),
+    }
+}
+
+// A property that could not be injected
+#[derive(Debug)]
+pub enum PropertyError {
+    Missing {
+        key: String,
+    },
+    Invalid {
+        key: String,
+        value: String,
+        expected: &'static str,
+        reason: String,
+    },
+}
+
+impl fmt::Display for PropertyError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            PropertyError::Missing { key } => write!(f, "property {} is not set and has no default", key),
+            PropertyError::Invalid { key, value, expected, reason } => {
+                write!(f, "property {}=\"{}\" is not a valid {}: {}", key, value, expected, reason)
+            }
+        }
+    }
+}
+
+impl std::error::Error for PropertyError {}
+
+// `key=value` lines; blank lines and lines starting with `#` or `!` are skipped
+fn parse_properties(contents: &str) -> HashMap<String, String> {
+    contents
+        .lines()
+        .map(str::trim)
+        .filter(|line| !line.is_empty() && !line.starts_with('#') && !line.starts_with('!'))
+        .filter_map(|line| {
+            let (key, value) = line.split_once('=').or_else(|| line.split_once(':'))?;
+            Some((key.trim().to_string(), value.trim().to_string()))
+        })
+        .collect()
+}
+
+// `rspring.main.lazy-initialization` -> `RSPRING_MAIN_LAZYINITIALIZATION`, like Spring's relaxed binding
+fn env_var_name(key: &str) -> String {
+    key.replace('.', "_").replace('-', "").to_uppercase()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn values_set_in_code_shadow_the_file() {
+        let mut env = Environment::new();
+        env.file = parse_properties("# comment\nserver.port = 8080\n! comment\napp.name: shop\n\n");
+        env.set("server.port", "9090");
+
+        assert_eq!(env.get("server.port").as_deref(), Some("9090"));
+        assert_eq!(env.get("app.name").as_deref(), Some("shop"));
+        assert_eq!(env.get_or("app.owner", "nobody"), "nobody");
+    }
+
+    #[test]
+    fn values_are_parsed_or_fall_back_to_their_default() {
+        let mut env = Environment::new();
+        env.set("server.port", " 8080 ");
+
+        assert_eq!(env.value::<u16>("server.port", Some("3000")).unwrap(), 8080);
+        assert_eq!(env.value::<u16>("server.backlog", Some("128")).unwrap(), 128);
+        assert_eq!(env.value::<String>("app.name", Some("")).unwrap(), "");
+        assert!(matches!(env.value::<u16>("server.backlog", None), Err(PropertyError::Missing { .. })));
+        assert_eq!(env.duration("server.timeout", Some("30s")).unwrap(), Duration::from_secs(30));
+    }
+
+    #[test]
+    fn values_that_do_not_parse_are_reported() {
+        let mut env = Environment::new();
+        env.set("server.port", "eighty");
+        env.set("server.timeout", "30 seconds");
+
+        let err = env.value::<u16>("server.port", Some("3000")).unwrap_err();
+        assert_eq!(err.to_string(), "property server.port=\"eighty\" is not a valid u16: invalid digit found in string");
+        // Defaults are parsed like the properties
+        let err = env.value::<bool>("app.enabled", Some("yes")).unwrap_err();
+        assert!(matches!(err, PropertyError::Invalid { expected: "bool", .. }), "{}", err);
+        let err = env.duration("server.timeout", None).unwrap_err();
+        assert_eq!(err.to_string(), "property server.timeout=\"30 seconds\" is not a valid duration: unknown unit \"seconds\", expected ms, s, m, h or d");
+    }
+
+    #[test]
+    fn environment_variables_are_named_after_the_key() {
+        assert_eq!(env_var_name("server.port"), "SERVER_PORT");
+        assert_eq!(env_var_name("rspring.main.lazy-initialization"), "RSPRING_MAIN_LAZYINITIALIZATION");
+    }
+
+    #[test]
+    fn environment_variables_sit_between_the_code_and_the_file() {
+        let mut env = Environment::new();
+        env.file = parse_properties("server.port=8080\napp.name=shop");
+        env.variables.insert("SERVER_PORT".to_string(), "9090".to_string());
+        env.variables.insert("APP_NAME".to_string(), "store".to_string());
+        env.set("app.name", "outlet");
+
+        assert_eq!(env.get("server.port").as_deref(), Some("9090"));
+        assert_eq!(env.get("app.name").as_deref(), Some("outlet"));
+    }
+
+    #[test]
+    fn new_environments_ignore_the_process_environment() {
+        let path = std::env::var("PATH").expect("PATH is set");
+        assert_eq!(Environment::load().get("path"), Some(path));
+        assert_eq!(Environment::new().get("path"), None);
+    }
+}
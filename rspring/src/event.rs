@@ -0,0 +1,132 @@
+use crate::{current, BuildError, Component, HookFuture, Instance, ServiceContainer};
+use std::any::{Any, TypeId};
+use std::collections::HashMap;
+use std::net::SocketAddr;
+use std::sync::{Arc, OnceLock, Weak};
+
+// A published event, shared by every listener receiving it
+pub type Event = Arc<dyn Any + Send + Sync>;
+
+// An #[event_listener] method, registered by #[service] and #[controller]
+pub struct EventListener {
+    // The component the method belongs to, which must be registered for it to be called
+    pub component: TypeId,
+    // `Type::method`, for error reporting
+    pub name: &'static str,
+    pub event: TypeId,
+    // Listeners of an event are called lowest first, then by name
+    pub order: i32,
+    // Run on a separate task instead of before `publish` returns
+    pub background: bool,
+    pub condition: Option<fn(&(dyn Any + Send + Sync)) -> bool>,
+    pub handle: fn(Arc<ServiceContainer>, Event) -> HookFuture,
+}
+
+inventory::collect!(EventListener);
+
+// Published by `Application::run` before the singletons are built; the components
+// listening to it are built first
+#[derive(Debug, Clone)]
+pub struct ApplicationStarting {
+    // The address the server is going to bind
+    pub addr: String,
+}
+
+// Every eager component is built and the routes are mounted
+#[derive(Debug, Clone)]
+pub struct ContextRefreshed;
+
+// The server accepts connections
+#[derive(Debug, Clone)]
+pub struct ApplicationReady {
+    // Where it actually listens, e.g. the port picked for "127.0.0.1:0"
+    pub local_addr: SocketAddr,
+}
+
+// Shutdown was requested; requests in flight are still served, then #[pre_destroy] runs
+#[derive(Debug, Clone)]
+pub struct ContextClosing;
+
+// The listeners of each event type, sorted
+fn listeners(event: TypeId) -> &'static [&'static EventListener] {
+    static LISTENERS: OnceLock<HashMap<TypeId, Vec<&'static EventListener>>> = OnceLock::new();
+    let by_event = LISTENERS.get_or_init(|| {
+        let mut by_event: HashMap<TypeId, Vec<&'static EventListener>> = HashMap::new();
+        for listener in inventory::iter::<EventListener> {
+            by_event.entry(listener.event).or_default().push(listener);
+        }
+        for found in by_event.values_mut() {
+            found.sort_by_key(|listener| (listener.order, listener.name));
+        }
+        by_event
+    });
+    by_event.get(&event).map(Vec::as_slice).unwrap_or_default()
+}
+
+// Injectable as `Arc<EventPublisher>` to notify #[event_listener] methods
+pub struct EventPublisher {
+    container: Weak<ServiceContainer>,
+}
+
+impl EventPublisher {
+    // Calls every listener of `E` whose component is registered, in order. Synchronous
+    // listeners have run when this returns and the first error stops the others;
+    // #[event_listener(async)] ones are spawned and only log their errors.
+    pub async fn publish<E: Any + Send + Sync>(&self, event: E) -> Result<(), BuildError> {
+        let container = current(&self.container);
+        let event: Event = Arc::new(event);
+
+        for listener in listeners(TypeId::of::<E>()) {
+            if container.candidates_for(listener.component).is_empty() {
+                continue;
+            }
+            if listener.condition.is_some_and(|condition| !condition(event.as_ref())) {
+                continue;
+            }
+
+            let handled = (listener.handle)(container.clone(), event.clone());
+            if !listener.background {
+                handled.await.map_err(|err| format!("event listener {} failed: {}", listener.name, err))?;
+                continue;
+            }
+            let name = listener.name;
+            let report = async move {
+                if let Err(err) = handled.await {
+                    eprintln!("[rspring] Event listener {} failed: {}", name, err);
+                }
+            };
+            match tokio::runtime::Handle::try_current() {
+                Ok(runtime) => drop(runtime.spawn(report)),
+                Err(_) => report.await,
+            }
+        }
+        Ok(())
+    }
+}
+
+impl ServiceContainer {
+    // The components whose listeners would receive an `E`
+    pub(crate) fn listening_to<E: Any>(&self) -> Vec<&'static Component> {
+        listeners(TypeId::of::<E>()).iter()
+            .flat_map(|listener| self.candidates_for(listener.component))
+            .copied()
+            .collect()
+    }
+
+    // Publish a framework event, logging listener errors rather than failing
+    pub(crate) async fn publish_event<E: Any + Send + Sync>(&self, event: E) {
+        let Some(publisher) = self.try_get::<EventPublisher>() else {
+            return;
+        };
+        if let Err(err) = publisher.publish(event).await {
+            eprintln!("[rspring] {}", err);
+        }
+    }
+}
+
+inventory::submit! {
+    Component::framework::<EventPublisher>("EventPublisher", |container| {
+        let publisher = EventPublisher { container: container.this.clone() };
+        Box::pin(async move { Ok(Arc::new(Arc::new(publisher)) as Instance) })
+    })
+}
@@ -0,0 +1,441 @@
+use crate::{Component, Environment, Instance};
+use std::collections::{HashMap, VecDeque};
+use std::fmt;
+use std::future::Future;
+use std::pin::Pin;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Mutex};
+use std::task::{Context, Poll};
+use tokio::sync::{oneshot, Notify};
+
+// What happens to a task submitted while the queue is full
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum RejectionPolicy {
+    // The new task is rejected
+    Abort,
+    // The oldest queued task is rejected to make room
+    DiscardOldest,
+}
+
+impl std::str::FromStr for RejectionPolicy {
+    type Err = String;
+
+    fn from_str(value: &str) -> Result<Self, Self::Err> {
+        match value {
+            "abort" => Ok(RejectionPolicy::Abort),
+            "discard-oldest" => Ok(RejectionPolicy::DiscardOldest),
+            _ => Err("expected \"abort\" or \"discard-oldest\"".to_string()),
+        }
+    }
+}
+
+// Why a task did not produce its result
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum TaskError {
+    // The queue was full or the executor shutting down
+    Rejected { executor: String },
+    Panicked { executor: String },
+}
+
+impl fmt::Display for TaskError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            TaskError::Rejected { executor } => write!(f, "task rejected by executor {}", executor),
+            TaskError::Panicked { executor } => write!(f, "task panicked on executor {}", executor),
+        }
+    }
+}
+
+impl std::error::Error for TaskError {}
+
+// Returned by #[async_method] methods; resolves to the method's result once it
+// ran. Dropping it does not cancel the task.
+pub struct TaskHandle<T> {
+    result: oneshot::Receiver<T>,
+    rejected: Arc<AtomicBool>,
+    executor: String,
+}
+
+impl<T> Future for TaskHandle<T> {
+    type Output = Result<T, TaskError>;
+
+    fn poll(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
+        Pin::new(&mut self.result).poll(cx).map(|result| {
+            // The sender is dropped without a result when the task is discarded or panics
+            result.map_err(|_| {
+                let executor = self.executor.clone();
+                if self.rejected.load(Ordering::Relaxed) {
+                    TaskError::Rejected { executor }
+                } else {
+                    TaskError::Panicked { executor }
+                }
+            })
+        })
+    }
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct ExecutorStats {
+    pub running: usize,
+    pub queued: usize,
+    pub completed: u64,
+    pub rejected: u64,
+}
+
+type Job = Pin<Box<dyn Future<Output = ()> + Send>>;
+
+// Runs up to `concurrency` tasks at once, keeping up to `queue_capacity` more
+// waiting; configured by `executor.<name>.concurrency`, `.queue-capacity` and
+// `.rejection-policy`
+pub struct TaskExecutor {
+    name: String,
+    concurrency: usize,
+    queue_capacity: usize,
+    policy: RejectionPolicy,
+    state: Mutex<ExecutorState>,
+    idle: Notify,
+}
+
+struct ExecutorState {
+    queue: VecDeque<(Job, Arc<AtomicBool>)>,
+    running: usize,
+    shutting_down: bool,
+    completed: u64,
+    rejected: u64,
+}
+
+impl TaskExecutor {
+    pub fn new(name: &str, concurrency: usize, queue_capacity: usize, policy: RejectionPolicy) -> Arc<Self> {
+        Arc::new(Self {
+            name: name.to_string(),
+            concurrency: concurrency.max(1),
+            queue_capacity,
+            policy,
+            state: Mutex::new(ExecutorState {
+                queue: VecDeque::new(),
+                running: 0,
+                shutting_down: false,
+                completed: 0,
+                rejected: 0,
+            }),
+            idle: Notify::new(),
+        })
+    }
+
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    pub fn stats(&self) -> ExecutorStats {
+        let state = self.state.lock().unwrap();
+        ExecutorStats {
+            running: state.running,
+            queued: state.queue.len(),
+            completed: state.completed,
+            rejected: state.rejected,
+        }
+    }
+
+    pub fn submit<T, F>(self: &Arc<Self>, task: F) -> TaskHandle<T>
+    where
+        T: Send + 'static,
+        F: Future<Output = T> + Send + 'static,
+    {
+        let (sender, result) = oneshot::channel();
+        let job = Box::pin(async move {
+            let _ = sender.send(task.await);
+        });
+        let rejected = Arc::new(AtomicBool::new(false));
+        self.enqueue(job, rejected.clone());
+        TaskHandle { result, rejected, executor: self.name.clone() }
+    }
+
+    // For blocking work, run on tokio's blocking threads while holding a slot
+    pub fn submit_blocking<T, F>(self: &Arc<Self>, task: F) -> TaskHandle<T>
+    where
+        T: Send + 'static,
+        F: FnOnce() -> T + Send + 'static,
+    {
+        self.submit(async move {
+            match tokio::task::spawn_blocking(task).await {
+                Ok(value) => value,
+                Err(err) => std::panic::resume_unwind(err.into_panic()),
+            }
+        })
+    }
+
+    fn enqueue(self: &Arc<Self>, job: Job, rejected: Arc<AtomicBool>) {
+        let mut state = self.state.lock().unwrap();
+        if state.shutting_down {
+            return self.reject(&mut state, rejected, "it is shutting down");
+        }
+        if state.running < self.concurrency {
+            state.running += 1;
+            drop(state);
+            return self.start(job);
+        }
+        if state.queue.len() >= self.queue_capacity {
+            if self.policy == RejectionPolicy::Abort || self.queue_capacity == 0 {
+                return self.reject(&mut state, rejected, "its queue is full");
+            }
+            if let Some((_, oldest)) = state.queue.pop_front() {
+                self.reject(&mut state, oldest, "its queue is full, dropping the oldest task");
+            }
+        }
+        state.queue.push_back((job, rejected));
+    }
+
+    fn reject(&self, state: &mut ExecutorState, rejected: Arc<AtomicBool>, reason: &str) {
+        rejected.store(true, Ordering::Relaxed);
+        state.rejected += 1;
+        eprintln!("[rspring] Executor {} rejected a task: {}", self.name, reason);
+    }
+
+    fn start(self: &Arc<Self>, job: Job) {
+        let slot = Slot(self.clone());
+        tokio::spawn(async move {
+            // Released when the job ends, even by panicking
+            let _slot = slot;
+            job.await;
+        });
+    }
+
+    // Start the next queued job in the slot that was freed
+    fn finished(self: &Arc<Self>) {
+        let mut state = self.state.lock().unwrap();
+        state.completed += 1;
+        match state.queue.pop_front() {
+            Some((job, _)) => {
+                drop(state);
+                self.start(job);
+            }
+            None => {
+                state.running -= 1;
+                if state.running == 0 {
+                    self.idle.notify_waiters();
+                }
+            }
+        }
+    }
+
+    // Rejects new tasks and waits for the running and queued ones to finish
+    pub async fn shutdown(&self) {
+        loop {
+            let idle = self.idle.notified();
+            {
+                let mut state = self.state.lock().unwrap();
+                state.shutting_down = true;
+                if state.running == 0 && state.queue.is_empty() {
+                    return;
+                }
+            }
+            idle.await;
+        }
+    }
+}
+
+struct Slot(Arc<TaskExecutor>);
+
+impl Drop for Slot {
+    fn drop(&mut self) {
+        self.0.finished();
+    }
+}
+
+// Injectable as `Arc<TaskExecutors>`; holds the executors used by #[async_method],
+// by name, created on first use from the `executor.<name>.*` properties. By default
+// they run as many tasks as there are CPUs, queue 100 more and abort the rest.
+pub struct TaskExecutors {
+    environment: Arc<Environment>,
+    executors: Mutex<HashMap<String, Arc<TaskExecutor>>>,
+}
+
+impl TaskExecutors {
+    pub const DEFAULT_QUEUE_CAPACITY: usize = 100;
+
+    pub fn executor(&self, name: &str) -> Arc<TaskExecutor> {
+        let mut executors = self.executors.lock().unwrap();
+        if let Some(executor) = executors.get(name) {
+            return executor.clone();
+        }
+        let cpus = std::thread::available_parallelism().map_or(1, |cpus| cpus.get());
+        let concurrency = self.environment
+            .value(&format!("executor.{}.concurrency", name), Some(&cpus.to_string()))
+            .unwrap_or_else(|err| {
+                eprintln!("[rspring] {}, executor {} runs {} tasks at once", err, name, cpus);
+                cpus
+            });
+        let queue_capacity = self.environment
+            .value(&format!("executor.{}.queue-capacity", name), Some(&Self::DEFAULT_QUEUE_CAPACITY.to_string()))
+            .unwrap_or_else(|err| {
+                eprintln!("[rspring] {}, executor {} queues up to {} tasks", err, name, Self::DEFAULT_QUEUE_CAPACITY);
+                Self::DEFAULT_QUEUE_CAPACITY
+            });
+        let policy = self.environment
+            .value(&format!("executor.{}.rejection-policy", name), Some("abort"))
+            .unwrap_or_else(|err| {
+                eprintln!("[rspring] {}, executor {} aborts tasks it cannot queue", err, name);
+                RejectionPolicy::Abort
+            });
+
+        let executor = TaskExecutor::new(name, concurrency, queue_capacity, policy);
+        executors.insert(name.to_string(), executor.clone());
+        executor
+    }
+
+    // Sorted by name
+    pub fn executors(&self) -> Vec<Arc<TaskExecutor>> {
+        let mut executors: Vec<_> = self.executors.lock().unwrap().values().cloned().collect();
+        executors.sort_by(|a, b| a.name.cmp(&b.name));
+        executors
+    }
+
+    // Shuts every executor down, waiting for their tasks
+    pub async fn shutdown(&self) {
+        for executor in self.executors() {
+            let stats = executor.stats();
+            if stats.running + stats.queued > 0 {
+                println!("[rspring] Waiting for {} tasks on executor {}", stats.running + stats.queued, executor.name);
+            }
+            executor.shutdown().await;
+        }
+    }
+}
+
+inventory::submit! {
+    Component::framework::<TaskExecutors>("TaskExecutors", |container| {
+        let executors = TaskExecutors {
+            environment: container.environment.clone(),
+            executors: Mutex::new(HashMap::new()),
+        };
+        Box::pin(async move { Ok(Arc::new(Arc::new(executors)) as Instance) })
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::atomic::AtomicUsize;
+    use tokio::sync::watch;
+
+    // A task that runs until `gate` opens
+    fn blocked(executor: &Arc<TaskExecutor>, gate: &watch::Receiver<bool>) -> TaskHandle<()> {
+        let mut gate = gate.clone();
+        executor.submit(async move {
+            let _ = gate.wait_for(|open| *open).await;
+        })
+    }
+
+    // Lets the spawned tasks run until they wait
+    async fn settle() {
+        for _ in 0..10 {
+            tokio::task::yield_now().await;
+        }
+    }
+
+    #[tokio::test]
+    async fn runs_at_most_concurrency_tasks_at_once() {
+        let executor = TaskExecutor::new("test", 2, 10, RejectionPolicy::Abort);
+        let (open, gate) = watch::channel(false);
+        let running = Arc::new(AtomicUsize::new(0));
+        let most = Arc::new(AtomicUsize::new(0));
+        let handles: Vec<_> = (0..5)
+            .map(|_| {
+                let (running, most, mut gate) = (running.clone(), most.clone(), gate.clone());
+                executor.submit(async move {
+                    most.fetch_max(running.fetch_add(1, Ordering::SeqCst) + 1, Ordering::SeqCst);
+                    let _ = gate.wait_for(|open| *open).await;
+                    running.fetch_sub(1, Ordering::SeqCst);
+                })
+            })
+            .collect();
+
+        settle().await;
+        assert_eq!(executor.stats(), ExecutorStats { running: 2, queued: 3, completed: 0, rejected: 0 });
+        open.send(true).unwrap();
+        for handle in handles {
+            handle.await.unwrap();
+        }
+        assert_eq!(most.load(Ordering::SeqCst), 2);
+        assert_eq!(executor.stats(), ExecutorStats { running: 0, queued: 0, completed: 5, rejected: 0 });
+    }
+
+    #[tokio::test]
+    async fn a_full_queue_aborts_new_tasks() {
+        let executor = TaskExecutor::new("test", 1, 1, RejectionPolicy::Abort);
+        let (open, gate) = watch::channel(false);
+        let running = blocked(&executor, &gate);
+        let queued = blocked(&executor, &gate);
+        let aborted = blocked(&executor, &gate);
+
+        assert_eq!(aborted.await, Err(TaskError::Rejected { executor: "test".to_string() }));
+        open.send(true).unwrap();
+        assert_eq!(running.await, Ok(()));
+        assert_eq!(queued.await, Ok(()));
+        assert_eq!(executor.stats().rejected, 1);
+    }
+
+    #[tokio::test]
+    async fn a_full_queue_discards_its_oldest_task() {
+        let executor = TaskExecutor::new("test", 1, 1, RejectionPolicy::DiscardOldest);
+        let (open, gate) = watch::channel(false);
+        let running = blocked(&executor, &gate);
+        let oldest = blocked(&executor, &gate);
+        let newest = executor.submit(async { "newest" });
+
+        assert_eq!(oldest.await, Err(TaskError::Rejected { executor: "test".to_string() }));
+        open.send(true).unwrap();
+        assert_eq!(running.await, Ok(()));
+        assert_eq!(newest.await, Ok("newest"));
+        assert_eq!(executor.stats(), ExecutorStats { running: 0, queued: 0, completed: 2, rejected: 1 });
+    }
+
+    #[tokio::test]
+    async fn shutdown_waits_for_running_and_queued_tasks() {
+        let executor = TaskExecutor::new("test", 1, 10, RejectionPolicy::Abort);
+        let (open, gate) = watch::channel(false);
+        let handles = [blocked(&executor, &gate), blocked(&executor, &gate)];
+        let shutdown = tokio::spawn({
+            let executor = executor.clone();
+            async move { executor.shutdown().await }
+        });
+
+        settle().await;
+        assert!(!shutdown.is_finished());
+        let late = executor.submit(async {});
+        assert_eq!(late.await, Err(TaskError::Rejected { executor: "test".to_string() }));
+
+        open.send(true).unwrap();
+        shutdown.await.unwrap();
+        assert_eq!(executor.stats(), ExecutorStats { running: 0, queued: 0, completed: 2, rejected: 1 });
+        for handle in handles {
+            assert_eq!(handle.await, Ok(()));
+        }
+    }
+
+    #[test]
+    fn executors_are_configured_and_shared_by_name() {
+        let mut environment = Environment::new();
+        environment.set("executor.mail.concurrency", "3");
+        environment.set("executor.mail.queue-capacity", "7");
+        environment.set("executor.mail.rejection-policy", "discard-oldest");
+        environment.set("executor.reports.concurrency", "many");
+        let executors = TaskExecutors {
+            environment: Arc::new(environment),
+            executors: Mutex::new(HashMap::new()),
+        };
+
+        let mail = executors.executor("mail");
+        assert_eq!((mail.concurrency, mail.queue_capacity, mail.policy), (3, 7, RejectionPolicy::DiscardOldest));
+        assert!(Arc::ptr_eq(&mail, &executors.executor("mail")));
+
+        // Invalid or missing properties fall back to the defaults
+        let reports = executors.executor("reports");
+        let cpus = std::thread::available_parallelism().map_or(1, |cpus| cpus.get());
+        assert_eq!(reports.concurrency, cpus);
+        assert_eq!((reports.queue_capacity, reports.policy), (TaskExecutors::DEFAULT_QUEUE_CAPACITY, RejectionPolicy::Abort));
+
+        let names: Vec<_> = executors.executors().iter().map(|executor| executor.name().to_string()).collect();
+        assert_eq!(names, ["mail", "reports"]);
+    }
+}
@@ -0,0 +1,191 @@
+use crate::{DependencyKind, Scope};
+use std::fmt::Write;
+use std::time::Duration;
+
+// A registered component, see `ServiceContainer::beans`
+#[derive(Debug, Clone)]
+pub struct BeanInfo {
+    pub name: &'static str,
+    pub type_name: &'static str,
+    pub scope: Scope,
+    pub dependencies: Vec<BeanDependency>,
+    // `None` until built, and always for request and prototype components
+    pub build_duration: Option<Duration>,
+}
+
+// An edge to the component injected for one of a bean's dependencies
+#[derive(Debug, Clone)]
+pub struct BeanDependency {
+    pub name: &'static str,
+    // The type asked for, e.g. the trait object rather than the implementation
+    pub type_name: &'static str,
+    pub kind: DependencyKind,
+}
+
+// The components and their dependencies, sorted by name. Build durations are
+// left out of the exports so they stay the same from one run to the next.
+#[derive(Debug, Clone)]
+pub struct DependencyGraph {
+    beans: Vec<BeanInfo>,
+}
+
+impl DependencyGraph {
+    pub(crate) fn new(beans: Vec<BeanInfo>) -> Self {
+        Self { beans }
+    }
+
+    pub fn beans(&self) -> &[BeanInfo] {
+        &self.beans
+    }
+
+    // Graphviz, render with `dot -Tsvg`. Providers are dashed and optional
+    // dependencies dotted.
+    pub fn to_dot(&self) -> String {
+        let mut dot = String::from("digraph rspring {\n    rankdir=LR;\n    node [shape=box];\n");
+        for bean in &self.beans {
+            let label = format!("{}\n{}", bean.type_name, bean.scope);
+            let _ = writeln!(dot, "    {} [label={}];", dot_quote(bean.name), dot_quote(&label));
+        }
+        for bean in &self.beans {
+            for dep in &bean.dependencies {
+                let style = match dep.kind {
+                    DependencyKind::One | DependencyKind::All => "",
+                    DependencyKind::Optional => " [style=dotted]",
+                    DependencyKind::Provider => " [style=dashed]",
+                };
+                let _ = writeln!(dot, "    {} -> {}{};", dot_quote(bean.name), dot_quote(dep.name), style);
+            }
+        }
+        dot.push_str("}\n");
+        dot
+    }
+
+    pub fn to_json(&self) -> String {
+        let mut json = String::from("{\n  \"beans\": [");
+        for (i, bean) in self.beans.iter().enumerate() {
+            let separator = if i == 0 { "" } else { "," };
+            let _ = write!(
+                json,
+                "{}\n    {{\n      \"name\": {},\n      \"type\": {},\n      \"scope\": \"{}\",\n      \"dependencies\": [",
+                separator,
+                json_quote(bean.name),
+                json_quote(bean.type_name),
+                bean.scope,
+            );
+            for (j, dep) in bean.dependencies.iter().enumerate() {
+                let separator = if j == 0 { "" } else { "," };
+                let _ = write!(
+                    json,
+                    "{}\n        {{ \"name\": {}, \"type\": {}, \"kind\": \"{}\" }}",
+                    separator,
+                    json_quote(dep.name),
+                    json_quote(dep.type_name),
+                    kind_name(dep.kind),
+                );
+            }
+            json.push_str(if bean.dependencies.is_empty() { "]\n    }" } else { "\n      ]\n    }" });
+        }
+        json.push_str(if self.beans.is_empty() { "]\n}\n" } else { "\n  ]\n}\n" });
+        json
+    }
+}
+
+fn kind_name(kind: DependencyKind) -> &'static str {
+    match kind {
+        DependencyKind::One => "one",
+        DependencyKind::All => "all",
+        DependencyKind::Optional => "optional",
+        DependencyKind::Provider => "provider",
+    }
+}
+
+fn dot_quote(value: &str) -> String {
+    format!("\"{}\"", value.replace('\\', "\\\\").replace('"', "\\\"").replace('\n', "\\n"))
+}
+
+fn json_quote(value: &str) -> String {
+    let mut quoted = String::from("\"");
+    for c in value.chars() {
+        match c {
+            '"' => quoted.push_str("\\\""),
+            '\\' => quoted.push_str("\\\\"),
+            '\n' => quoted.push_str("\\n"),
+            c if c.is_control() => {
+                let _ = write!(quoted, "\\u{:04x}", c as u32);
+            }
+            c => quoted.push(c),
+        }
+    }
+    quoted.push('"');
+    quoted
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn graph() -> DependencyGraph {
+        DependencyGraph::new(vec![
+            BeanInfo {
+                name: "Cache<\"users\">",
+                type_name: "app::Cache<app::User>",
+                scope: Scope::Singleton,
+                dependencies: Vec::new(),
+                build_duration: Some(Duration::from_millis(3)),
+            },
+            BeanInfo {
+                name: "UserService",
+                type_name: "app::UserService",
+                scope: Scope::Request,
+                dependencies: vec![
+                    BeanDependency { name: "Cache<\"users\">", type_name: "app::Cache<app::User>", kind: DependencyKind::One },
+                    BeanDependency { name: "C:\\audit", type_name: "dyn app::Audit", kind: DependencyKind::Optional },
+                    BeanDependency { name: "Mailer", type_name: "app::Mailer", kind: DependencyKind::Provider },
+                ],
+                build_duration: None,
+            },
+        ])
+    }
+
+    #[test]
+    fn exports_to_dot() {
+        let expected = r#"digraph rspring {
+    rankdir=LR;
+    node [shape=box];
+    "Cache<\"users\">" [label="app::Cache<app::User>\nsingleton"];
+    "UserService" [label="app::UserService\nrequest"];
+    "UserService" -> "Cache<\"users\">";
+    "UserService" -> "C:\\audit" [style=dotted];
+    "UserService" -> "Mailer" [style=dashed];
+}
+"#;
+        assert_eq!(graph().to_dot(), expected);
+    }
+
+    #[test]
+    fn exports_to_json() {
+        let expected = r#"{
+  "beans": [
+    {
+      "name": "Cache<\"users\">",
+      "type": "app::Cache<app::User>",
+      "scope": "singleton",
+      "dependencies": []
+    },
+    {
+      "name": "UserService",
+      "type": "app::UserService",
+      "scope": "request",
+      "dependencies": [
+        { "name": "Cache<\"users\">", "type": "app::Cache<app::User>", "kind": "one" },
+        { "name": "C:\\audit", "type": "dyn app::Audit", "kind": "optional" },
+        { "name": "Mailer", "type": "app::Mailer", "kind": "provider" }
+      ]
+    }
+  ]
+}
+"#;
+        assert_eq!(graph().to_json(), expected);
+        assert_eq!(DependencyGraph::new(Vec::new()).to_json(), "{\n  \"beans\": []\n}\n");
+    }
+}
@@ -0,0 +1,179 @@
+use crate::{BuildError, Dependency, Instance, ServiceContainer, Unresolved};
+use std::any::{Any, TypeId};
+use std::collections::HashMap;
+use std::fmt;
+use std::future::Future;
+use std::pin::Pin;
+use std::sync::Arc;
+
+// The components called by the methods of a type wrapped by #[intercept],
+// #[cacheable], #[retryable], #[async_method] and the like, registered by the impl
+// block holding them
+pub struct WrappedMethods {
+    pub component: TypeId,
+    pub dependencies: &'static [Dependency],
+}
+
+inventory::collect!(WrappedMethods);
+
+// Held by a component with wrapped methods in a field named `collaborators`, which
+// the container fills with the components those methods call, resolved when it
+// builds the instance. An instance built by hand with `Collaborators::default()`
+// has none, so its methods run unwrapped.
+#[derive(Clone, Default)]
+pub struct Collaborators {
+    components: HashMap<TypeId, Instance>,
+}
+
+impl Collaborators {
+    // The components the wrapped methods of `T` call, from `container`
+    pub fn resolve<T: ?Sized + 'static>(container: &ServiceContainer) -> Result<Self, BuildError> {
+        let mut components = HashMap::new();
+        let wrapped = inventory::iter::<WrappedMethods>().filter(|wrapped| wrapped.component == TypeId::of::<T>());
+        for dep in wrapped.flat_map(|wrapped| wrapped.dependencies) {
+            let instance = container.try_instance(dep.type_id, dep.qualifier).map_err(|err| {
+                let problem = match err {
+                    Unresolved::Missing => "which is not registered".to_string(),
+                    Unresolved::Ambiguous(names) => format!("which is provided by {}", names.join(", ")),
+                };
+                format!("the methods of {} call {}, {}", std::any::type_name::<T>(), (dep.type_name)(), problem)
+            })?;
+            components.insert(dep.type_id, instance);
+        }
+        Ok(Self { components })
+    }
+
+    pub fn get<T: ?Sized + 'static + Send + Sync>(&self) -> Option<Arc<T>> {
+        let instance = self.components.get(&TypeId::of::<T>())?;
+        Some(instance.downcast_ref::<Arc<T>>().expect("Downcast failed").clone())
+    }
+}
+
+impl fmt::Debug for Collaborators {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "Collaborators({})", self.components.len())
+    }
+}
+
+// A call to a method wrapped by #[intercept]
+#[derive(Debug, Clone)]
+pub struct Invocation {
+    pub component: &'static str,
+    pub method: &'static str,
+    // `{:?}` of each argument, or `<type name>` when it is not Debug
+    pub args: Vec<String>,
+}
+
+// The rest of the chain: the next interceptor, or the method itself
+pub struct Proceed<'a> {
+    call: Pin<Box<dyn Future<Output = ReturnValue> + Send + 'a>>,
+}
+
+impl<'a> Proceed<'a> {
+    pub fn new(call: impl Future<Output = ReturnValue> + Send + 'a) -> Self {
+        Self { call: Box::pin(call) }
+    }
+
+    pub async fn proceed(self) -> ReturnValue {
+        self.call.await
+    }
+}
+
+// Registered as a component and named in #[intercept(..)]. It can look at the
+// invocation, call `proceed` and change what the method returned, or return a
+// value of the method's return type without calling it at all.
+pub trait Interceptor: Send + Sync {
+    fn intercept(&self, invocation: &Invocation, proceed: Proceed<'_>) -> impl Future<Output = ReturnValue> + Send;
+}
+
+// What an intercepted method returned, with its type erased
+pub struct ReturnValue {
+    value: Box<dyn Any + Send>,
+    debug: fn(&(dyn Any + Send)) -> String,
+}
+
+impl ReturnValue {
+    pub fn new<T: Any + Send + fmt::Debug>(value: T) -> Self {
+        Self::from_parts(value, |value| format!("{:?}", value.downcast_ref::<T>().unwrap()))
+    }
+
+    // For a value that is not Debug, shown as its type name
+    pub fn opaque<T: Any + Send>(value: T) -> Self {
+        Self::from_parts(value, |_| format!("<{}>", std::any::type_name::<T>()))
+    }
+
+    #[doc(hidden)]
+    pub fn from_parts<T: Any + Send>(value: T, debug: fn(&(dyn Any + Send)) -> String) -> Self {
+        Self { value: Box::new(value), debug }
+    }
+
+    pub fn is<T: Any>(&self) -> bool {
+        self.value.is::<T>()
+    }
+
+    pub fn downcast_ref<T: Any>(&self) -> Option<&T> {
+        self.value.downcast_ref()
+    }
+
+    pub fn downcast_mut<T: Any>(&mut self) -> Option<&mut T> {
+        self.value.downcast_mut()
+    }
+
+    pub fn take<T: Any>(self) -> Result<T, Self> {
+        match self.value.downcast::<T>() {
+            Ok(value) => Ok(*value),
+            Err(value) => Err(Self { value, debug: self.debug }),
+        }
+    }
+}
+
+impl fmt::Debug for ReturnValue {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(&(self.debug)(self.value.as_ref()))
+    }
+}
+
+// Used by the generated code to show arguments and return values whether or not
+// they implement Debug: `(&&Describe(&value)).describe()` picks the Debug impl on
+// `&Describe` when it applies, and falls back to the one on `Describe` otherwise.
+pub struct Describe<'a, T: ?Sized>(pub &'a T);
+
+pub trait DescribeDebug {
+    fn describe(&self) -> String;
+}
+
+impl<T: fmt::Debug + ?Sized> DescribeDebug for &Describe<'_, T> {
+    fn describe(&self) -> String {
+        format!("{:?}", self.0)
+    }
+}
+
+pub trait DescribeOpaque {
+    fn describe(&self) -> String;
+}
+
+impl<T: ?Sized> DescribeOpaque for Describe<'_, T> {
+    fn describe(&self) -> String {
+        format!("<{}>", std::any::type_name::<T>())
+    }
+}
+
+pub trait DebugFn {
+    fn debug_fn(&self) -> fn(&(dyn Any + Send)) -> String;
+}
+
+impl<T: fmt::Debug + Any> DebugFn for &Describe<'_, T> {
+    fn debug_fn(&self) -> fn(&(dyn Any + Send)) -> String {
+        |value| format!("{:?}", value.downcast_ref::<T>().unwrap())
+    }
+}
+
+pub trait OpaqueFn {
+    fn debug_fn(&self) -> fn(&(dyn Any + Send)) -> String;
+}
+
+impl<T: Any> OpaqueFn for Describe<'_, T> {
+    fn debug_fn(&self) -> fn(&(dyn Any + Send)) -> String {
+        |_| format!("<{}>", std::any::type_name::<T>())
+    }
+}
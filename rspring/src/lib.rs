@@ -1,11 +1,101 @@
 use axum::Router;
-use std::sync::Arc;
-use std::collections::HashMap;
+use std::sync::{Arc, Mutex, OnceLock, Weak};
+use std::future::Future;
+use std::marker::PhantomData;
+use std::pin::Pin;
+use std::task::{Context, Poll, Waker};
+use std::collections::{HashMap, HashSet};
 use std::any::{Any, TypeId};
+use std::fmt;
+use std::time::{Duration, Instant};
+use tokio::runtime::RuntimeFlavor;
 
 pub use axum;
 pub use inventory;
 
+mod builder;
+mod cache;
+mod condition;
+mod environment;
+mod event;
+mod executor;
+mod graph;
+mod intercept;
+mod resilience;
+mod schedule;
+#[cfg(test)]
+mod test_support;
+pub use builder::ContainerBuilder;
+pub use cache::{Cache, CacheManager, CacheStats};
+pub use condition::{Condition, ConditionOutcome};
+pub use environment::{Environment, PropertyError};
+pub use event::{
+    ApplicationReady, ApplicationStarting, ContextClosing, ContextRefreshed, Event, EventListener, EventPublisher,
+};
+pub use executor::{ExecutorStats, RejectionPolicy, TaskError, TaskExecutor, TaskExecutors, TaskHandle};
+pub use graph::{BeanDependency, BeanInfo, DependencyGraph};
+pub use intercept::{Collaborators, Interceptor, Invocation, Proceed, ReturnValue, WrappedMethods};
+pub use resilience::{
+    Backoff, CallNotPermitted, CircuitBreaker, CircuitBreakerConfig, CircuitBreakerRegistry, CircuitMetrics, CircuitState,
+    RetryPolicy,
+};
+pub use schedule::{ScheduledTask, Scheduler, Trigger};
+
+// Used by the code generated for #[retryable], #[async_method] and the like, and
+// by the macros themselves
+#[doc(hidden)]
+pub mod __private {
+    pub use crate::environment::parse_duration;
+    pub use crate::intercept::{DebugFn, Describe, DescribeDebug, DescribeOpaque, OpaqueFn};
+    use crate::{Collaborators, RejectionPolicy, TaskExecutor, TaskExecutors};
+    use std::error::Error;
+    use std::sync::Arc;
+
+    // The executor of an #[async_method]; an instance built by hand has no
+    // TaskExecutors, so it runs the task right away on one of its own
+    pub fn executor(collaborators: &Collaborators, name: &str) -> Arc<TaskExecutor> {
+        match collaborators.get::<TaskExecutors>() {
+            Some(executors) => executors.executor(name),
+            None => TaskExecutor::new(name, usize::MAX, 0, RejectionPolicy::Abort),
+        }
+    }
+
+    // Whether `err` or one of its sources is a `C`, for #[retryable(retry_on = C)]
+    pub fn caused_by<C: Error + 'static>(err: Option<&(dyn Error + 'static)>) -> bool {
+        let mut err = err;
+        while let Some(cause) = err {
+            if cause.is::<C>() {
+                return true;
+            }
+            err = cause.source();
+        }
+        false
+    }
+
+    // `(&&ErrorRef(&err)).as_error()` for an error type, or a boxed `dyn Error`
+    pub struct ErrorRef<'a, E: ?Sized>(pub &'a E);
+
+    pub trait AsError<'a> {
+        fn as_error(&self) -> Option<&'a (dyn Error + 'static)>;
+    }
+
+    impl<'a, E: Error + 'static> AsError<'a> for &ErrorRef<'a, E> {
+        fn as_error(&self) -> Option<&'a (dyn Error + 'static)> {
+            Some(self.0)
+        }
+    }
+
+    pub trait AsBoxedError<'a> {
+        fn as_error(&self) -> Option<&'a (dyn Error + 'static)>;
+    }
+
+    impl<'a> AsBoxedError<'a> for ErrorRef<'a, Box<dyn Error + Send + Sync>> {
+        fn as_error(&self) -> Option<&'a (dyn Error + 'static)> {
+            Some(self.0.as_ref())
+        }
+    }
+}
+
 #[derive(Debug)]
 pub enum Method {
     GET,
@@ -17,6 +107,8 @@ pub enum Method {
 pub struct Route {
     pub path: &'static str,
     pub method: Method,
+    // The #[controller] handling the route, which may have been skipped by its conditions
+    pub controller: Option<TypeId>,
     pub setup: fn(Router) -> Router, 
 }
 
@@ -28,112 +120,1058 @@ impl Route {
 
 inventory::collect!(Route);
 
+// What a dependency wants from the components of its type
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum DependencyKind {
+    // Exactly one component, `Arc<T>`
+    One,
+    // Every component, in order, `Vec<Arc<T>>`
+    All,
+    // One component if registered, `Option<Arc<T>>`
+    Optional,
+    // Resolved on use, `Provider<T>`
+    Provider,
+}
+
+// A dependency edge: the type a component needs, plus its name for error reporting
+pub struct Dependency {
+    pub type_id: TypeId,
+    pub type_name: fn() -> &'static str,
+    pub kind: DependencyKind,
+    pub qualifier: Option<&'static str>,
+}
+
+impl Dependency {
+    pub const fn of<T: ?Sized + 'static>() -> Self {
+        Self {
+            type_id: TypeId::of::<T>(),
+            type_name: std::any::type_name::<T>,
+            kind: DependencyKind::One,
+            qualifier: None,
+        }
+    }
+
+    // A dependency on every component of type `T`
+    pub const fn all<T: ?Sized + 'static>() -> Self {
+        Self {
+            kind: DependencyKind::All,
+            ..Self::of::<T>()
+        }
+    }
+
+    pub const fn optional<T: ?Sized + 'static>() -> Self {
+        Self {
+            kind: DependencyKind::Optional,
+            ..Self::of::<T>()
+        }
+    }
+
+    pub const fn provider<T: ?Sized + 'static>() -> Self {
+        Self {
+            kind: DependencyKind::Provider,
+            ..Self::of::<T>()
+        }
+    }
+
+    // Only the component registered under `name` satisfies this dependency, see #[qualifier]
+    pub const fn named(mut self, name: &'static str) -> Self {
+        self.qualifier = Some(name);
+        self
+    }
+}
+
+// An extra type a component can be injected as, usually a trait object
+pub struct ProvidedType {
+    pub type_id: TypeId,
+    pub type_name: fn() -> &'static str,
+    pub cast: fn(Instance) -> Instance,
+}
+
+// How long a built instance lives
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Scope {
+    // One shared instance, built at startup
+    Singleton,
+    // One instance per HTTP request
+    Request,
+    // A new instance on every `get`
+    Prototype,
+}
+
+impl fmt::Display for Scope {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            Scope::Singleton => "singleton",
+            Scope::Request => "request",
+            Scope::Prototype => "prototype",
+        })
+    }
+}
+
+// Instances hold an `Arc<T>` so trait objects can be stored alongside concrete types
+pub type Instance = Arc<dyn Any + Send + Sync>;
+
+// Error returned by a fallible constructor
+pub type BuildError = Box<dyn std::error::Error + Send + Sync>;
+
+// Constructors may be async, so building a component yields a future
+pub type BuildFuture<'a> = Pin<Box<dyn Future<Output = Result<Instance, BuildError>> + Send + 'a>>;
+
+// A #[post_construct] or #[pre_destroy] method, called with the built instance
+pub type HookFuture = Pin<Box<dyn Future<Output = Result<(), BuildError>> + Send>>;
+
 // Component metadata - no factories!
 pub struct Component {
     pub name: &'static str,
     pub type_id: TypeId,
-    pub dependencies: &'static [TypeId],
-    pub build: fn(&ServiceContainer) -> Arc<dyn Any + Send + Sync>,
+    pub type_name: fn() -> &'static str,
+    // Where the component was declared, for `ContainerBuilder::module`
+    pub module_path: &'static str,
+    pub tags: &'static [&'static str],
+    pub scope: Scope,
+    pub primary: bool,
+    // Position when injected as part of a collection, lowest first
+    pub order: i32,
+    // Singleton built on first use instead of at startup
+    pub lazy: bool,
+    // All must hold for the component to be registered
+    pub conditions: &'static [Condition],
+    pub provides: &'static [ProvidedType],
+    pub dependencies: &'static [Dependency],
+    pub build: fn(&ServiceContainer) -> BuildFuture<'_>,
+    pub post_construct: Option<fn(Instance) -> HookFuture>,
+    pub pre_destroy: Option<fn(Instance) -> HookFuture>,
 }
 
 inventory::collect!(Component);
 
+impl Component {
+    // A component of the framework itself, such as the EventPublisher: an eager
+    // singleton without dependencies, last among those of its type
+    pub(crate) const fn framework<T: 'static>(name: &'static str, build: fn(&ServiceContainer) -> BuildFuture<'_>) -> Self {
+        Self {
+            name,
+            type_id: TypeId::of::<T>(),
+            type_name: std::any::type_name::<T>,
+            module_path: module_path!(),
+            tags: &[],
+            scope: Scope::Singleton,
+            primary: false,
+            order: i32::MAX,
+            lazy: false,
+            conditions: &[],
+            provides: &[],
+            dependencies: &[],
+            build,
+            post_construct: None,
+            pre_destroy: None,
+        }
+    }
+}
+
+// Everything that can stop the container from being built
+#[derive(Debug)]
+pub enum ContainerError {
+    MissingDependency {
+        component: &'static str,
+        dependency: &'static str,
+        qualifier: Option<&'static str>,
+    },
+    AmbiguousDependency {
+        component: &'static str,
+        dependency: &'static str,
+        candidates: Vec<&'static str>,
+    },
+    CircularDependency {
+        path: Vec<&'static str>,
+    },
+    DuplicateComponent {
+        component: &'static str,
+        existing: &'static str,
+    },
+    ScopeMismatch {
+        component: &'static str,
+        dependency: &'static str,
+    },
+    ConstructionFailed {
+        component: &'static str,
+        source: BuildError,
+    },
+    InvalidSchedule {
+        task: &'static str,
+        reason: String,
+    },
+    // `try_build` called on a current-thread tokio runtime, which cannot build
+    // the components while the thread blocks on them
+    BlockingBuild,
+}
+
+impl fmt::Display for ContainerError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ContainerError::MissingDependency { component, dependency, qualifier: None } => {
+                write!(f, "{} depends on {}, which is not registered", component, dependency)
+            }
+            ContainerError::MissingDependency { component, dependency, qualifier: Some(name) } => {
+                write!(f, "{} depends on {} named \"{}\", which is not registered", component, dependency, name)
+            }
+            ContainerError::AmbiguousDependency { component, dependency, candidates } => {
+                write!(
+                    f,
+                    "{} depends on {}, but it is provided by {}; mark one #[primary] or use #[qualifier]",
+                    component, dependency, candidates.join(", ")
+                )
+            }
+            ContainerError::CircularDependency { path } => {
+                write!(f, "circular dependency: {}", path.join(" -> "))
+            }
+            ContainerError::DuplicateComponent { component, existing } => {
+                write!(f, "{} is registered with the same type or name as {}", component, existing)
+            }
+            ContainerError::ScopeMismatch { component, dependency } => {
+                write!(f, "singleton {} cannot depend on {}, which only lives for a request", component, dependency)
+            }
+            ContainerError::ConstructionFailed { component, source } => {
+                write!(f, "failed to construct {}: {}", component, source)
+            }
+            ContainerError::InvalidSchedule { task, reason } => {
+                write!(f, "cannot schedule {}: {}", task, reason)
+            }
+            ContainerError::BlockingBuild => {
+                write!(f, "cannot block on a current-thread tokio runtime to build the container; use try_build_async().await")
+            }
+        }
+    }
+}
+
+impl std::error::Error for ContainerError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            ContainerError::ConstructionFailed { source, .. } => Some(source.as_ref()),
+            _ => None,
+        }
+    }
+}
+
 // Container to hold all components
 pub struct ServiceContainer {
-    components: HashMap<TypeId, Arc<dyn Any + Send + Sync>>,
+    // Singletons keyed by their concrete type, filled at startup or on first use when lazy
+    components: HashMap<TypeId, OnceLock<Instance>>,
+    // Singletons in the order they were built, destroyed in reverse
+    built: Mutex<Vec<&'static Component>>,
+    // Time spent building each singleton, including its #[post_construct]
+    durations: Mutex<HashMap<TypeId, Duration>>,
+    // Every registered component, sorted by name
+    registered: Vec<&'static Component>,
+    // Every type a component can be injected as, with the components providing it
+    candidates: HashMap<TypeId, Vec<&'static Component>>,
+    request: Option<RequestScope>,
+    // Resolves the types no component of this container provides
+    parent: Option<Arc<ServiceContainer>>,
+    environment: Arc<Environment>,
+    conditions: Vec<ConditionOutcome>,
+    // Handed out to providers, which resolve after construction
+    this: Weak<ServiceContainer>,
+    // The components standing in for overrides, which the fields above point to
+    overridden: Vec<Arc<Component>>,
+}
+
+// Instances of request-scoped components, alive for a single request
+struct RequestScope {
+    root: Arc<ServiceContainer>,
+    // In creation order, destroyed in reverse when the request ends
+    instances: Mutex<Vec<(&'static Component, Instance)>>,
+}
+
+// Spring's name for it: a container built from a selection of the components,
+// possibly on top of a parent, see `ServiceContainer::builder`
+pub type ApplicationContext = ServiceContainer;
+
+tokio::task_local! {
+    // The request scope of the request being handled, see `ServiceContainer::scoped`
+    static CURRENT_REQUEST: Arc<ServiceContainer>;
 }
 
 impl ServiceContainer {
-    fn new() -> Self {
-        Self {
-            components: HashMap::new(),
-        }
-    }
-    
     pub fn build() -> Arc<Self> {
-        let mut container = Self::new();
-        let all_components: Vec<&Component> = inventory::iter::<Component>().collect();
+        Self::try_build().unwrap_or_else(|err| panic!("{}", err))
+    }
+
+    pub fn try_build() -> Result<Arc<Self>, ContainerError> {
+        Self::builder().try_build()
+    }
+
+    // Builds every singleton, awaiting async constructors on the current runtime
+    pub async fn try_build_async() -> Result<Arc<Self>, ContainerError> {
+        Self::try_build_with(Environment::load()).await
+    }
+
+    pub async fn try_build_with(environment: Environment) -> Result<Arc<Self>, ContainerError> {
+        Self::builder().environment(environment).try_build_async().await
+    }
+
+    // Starts from the registered components, letting tests override or exclude some
+    pub fn builder() -> ContainerBuilder {
+        ContainerBuilder::new()
+    }
+
+    // Builds the given components, along with the `overrides`, which are already instantiated
+    async fn assemble(
+        environment: Environment,
+        mut registered: Vec<&'static Component>,
+        overrides: Vec<(Arc<Component>, Instance)>,
+        parent: Option<Arc<ServiceContainer>>,
+        starting: Option<ApplicationStarting>,
+    ) -> Result<Arc<Self>, ContainerError> {
+        let (overridden, instances): (Vec<_>, Vec<_>) = overrides.into_iter().unzip();
+        // SAFETY: the container keeps the override components for as long as it lives,
+        // and their references only go to the container itself and to its children,
+        // which keep it alive
+        registered.extend(overridden.iter().map(|comp| unsafe { &*Arc::as_ptr(comp) }));
+        let inherited = parent.as_ref().map(|parent| parent.visible_components()).unwrap_or_default();
+        let (all_components, conditions) = condition::evaluate(registered, &inherited, &environment);
+
+        if !conditions.is_empty() {
+            println!("[rspring] Conditions:");
+            for outcome in &conditions {
+                let mark = if outcome.included { "included" } else { "skipped" };
+                println!("[rspring]   {} {}: {}", outcome.component, mark, outcome.reason);
+            }
+        }
         
         println!("[rspring] Building {} components...", all_components.len());
         
-        // Build in dependency order
-        let mut remaining = all_components;
-        let mut attempts = 0;
+        let candidates = index(&all_components)?;
+        // The whole graph is validated even when nothing is built up front
+        let layers = build_order(&all_components, &candidates, parent.as_deref())?;
+        let lazy_by_default = environment.get_bool("rspring.main.lazy-initialization").unwrap_or(false);
+        let mut registered = all_components;
+        registered.sort_by_key(|comp| comp.name);
+        let container = Arc::new_cyclic(|this| Self {
+            components: registered.iter()
+                .filter(|comp| comp.scope == Scope::Singleton)
+                .map(|comp| (comp.type_id, OnceLock::new()))
+                .collect(),
+            built: Mutex::new(Vec::new()),
+            durations: Mutex::new(HashMap::new()),
+            registered,
+            candidates,
+            request: None,
+            parent,
+            environment: Arc::new(environment),
+            conditions,
+            this: this.clone(),
+            overridden,
+        });
+
+        for (comp, instance) in container.overridden.iter().zip(instances) {
+            println!("[rspring]   ✓ {} (override)", comp.name);
+            let _ = container.components[&comp.type_id].set(instance);
+        }
+
+        let started = Instant::now();
+        if let Some(event) = starting {
+            // The publisher, the listeners and what they need are built first, and
+            // fail the startup like any other singleton
+            let mut listening = container.listening_to::<ApplicationStarting>();
+            listening.extend(container.candidates_for(TypeId::of::<EventPublisher>()));
+            let needed = with_dependencies(listening, &container.candidates);
+            container.build_layers(&layers, |comp| needed.contains(&comp.type_id)).await?;
+            container.publish_event(event).await;
+        }
+
+        // Narrower scopes and lazy singletons are built on demand
+        container.build_layers(&layers, |comp| !comp.lazy && !lazy_by_default).await?;
+        println!("[rspring] Components built in {:.2?}", started.elapsed());
         
-        while !remaining.is_empty() && attempts < 100 {
-            let mut built = Vec::new();
-            
-            for (i, comp) in remaining.iter().enumerate() {
-                // Can we build this? Check if all deps are ready
-                let ready = comp.dependencies.iter()
-                    .all(|dep| container.components.contains_key(dep));
-                
-                if ready {
-                    println!("[rspring]   ✓ {}", comp.name);
-                    let instance = (comp.build)(&container);
-                    container.components.insert(comp.type_id, instance);
-                    built.push(i);
+        Ok(container)
+    }
+
+    // Build the singletons of `layers` selected by `eager` that are not built yet.
+    // Components of a layer only depend on earlier layers, so they are built
+    // concurrently when running inside a tokio runtime.
+    async fn build_layers(
+        self: &Arc<Self>,
+        layers: &[Vec<&'static Component>],
+        eager: impl Fn(&Component) -> bool,
+    ) -> Result<(), ContainerError> {
+        let runtime = tokio::runtime::Handle::try_current().ok();
+        for layer in layers {
+            let selected = layer.iter().copied().filter(|comp| {
+                comp.scope == Scope::Singleton && eager(comp)
+                    // Already built on demand, e.g. through a provider
+                    && self.components[&comp.type_id].get().is_none()
+            });
+
+            let mut results = Vec::new();
+            match &runtime {
+                Some(runtime) => {
+                    let tasks: Vec<_> = selected
+                        .map(|comp| (comp, runtime.spawn(self.clone().initialize(comp))))
+                        .collect();
+                    for (comp, task) in tasks {
+                        let result = task.await.unwrap_or_else(|err| std::panic::resume_unwind(err.into_panic()));
+                        results.push((comp, result));
+                    }
+                }
+                None => {
+                    for comp in selected {
+                        results.push((comp, self.clone().initialize(comp).await));
+                    }
                 }
             }
-            
-            // Remove built ones
-            for &i in built.iter().rev() {
-                remaining.remove(i);
+
+            // Reported in name order, whichever finished first
+            for (comp, result) in results {
+                let (instance, elapsed) = result
+                    .map_err(|source| ContainerError::ConstructionFailed { component: comp.name, source })?;
+                println!("[rspring]   ✓ {} ({:.2?})", comp.name, elapsed);
+                // Unless a provider built it on demand meanwhile
+                if self.components[&comp.type_id].set(instance).is_ok() {
+                    self.built.lock().unwrap().push(comp);
+                    self.durations.lock().unwrap().insert(comp.type_id, elapsed);
+                }
             }
-            
-            attempts += 1;
         }
-        
-        if !remaining.is_empty() {
-            panic!("Circular dependency! Can't build: {:?}", 
-                remaining.iter().map(|c| c.name).collect::<Vec<_>>());
+        Ok(())
+    }
+
+    // Build a singleton at startup and run its #[post_construct], timing both
+    async fn initialize(self: Arc<Self>, comp: &'static Component) -> Result<(Instance, Duration), BuildError> {
+        let started = Instant::now();
+        let instance = (comp.build)(&self).await?;
+        if let Some(post_construct) = comp.post_construct {
+            post_construct(instance.clone()).await?;
         }
-        
-        Arc::new(container)
+        Ok((instance, started.elapsed()))
+    }
+
+    pub fn environment(&self) -> &Environment {
+        &self.environment
+    }
+
+    pub fn parent(&self) -> Option<&Arc<ServiceContainer>> {
+        self.root().parent.as_ref()
+    }
+
+    // Why each conditional component was included or skipped
+    pub fn condition_report(&self) -> &[ConditionOutcome] {
+        &self.root().conditions
+    }
+
+    // Every registered component in name order, with the components it depends on
+    // and how long it took to build, if it was built as a singleton yet
+    pub fn beans(&self) -> impl Iterator<Item = BeanInfo> + '_ {
+        let root = self.root();
+        root.registered.iter().map(move |comp| BeanInfo {
+            name: comp.name,
+            type_name: (comp.type_name)(),
+            scope: comp.scope,
+            dependencies: root.dependencies_of(comp),
+            build_duration: root.durations.lock().unwrap().get(&comp.type_id).copied(),
+        })
+    }
+
+    // The component graph, for export to Graphviz DOT or JSON
+    pub fn dependency_graph(&self) -> DependencyGraph {
+        DependencyGraph::new(self.beans().collect())
+    }
+
+    // The components each dependency of `comp` resolves to; providers are
+    // included, optional dependencies only when something is registered
+    fn dependencies_of(&self, comp: &Component) -> Vec<BeanDependency> {
+        let mut dependencies = Vec::new();
+        for dep in comp.dependencies {
+            let found = self.candidates_for(dep.type_id);
+            let targets = match dep.kind {
+                DependencyKind::All => found.to_vec(),
+                _ => select(found, dep.qualifier).map(|target| vec![target]).unwrap_or_default(),
+            };
+            dependencies.extend(targets.into_iter().map(|target| BeanDependency {
+                name: target.name,
+                type_name: (dep.type_name)(),
+                kind: dep.kind,
+            }));
+        }
+        dependencies
+    }
+
+    // Runs #[pre_destroy] hooks: for a request scope on its request-scoped
+    // instances, otherwise on every singleton, dependents before their dependencies
+    pub async fn close(&self) {
+        let destroyed: Vec<(&'static Component, Instance)> = match &self.request {
+            Some(scope) => std::mem::take(&mut *scope.instances.lock().unwrap()),
+            None => {
+                let built = std::mem::take(&mut *self.built.lock().unwrap());
+                built.into_iter()
+                    .filter_map(|comp| Some((comp, self.components[&comp.type_id].get()?.clone())))
+                    .collect()
+            }
+        };
+
+        for (comp, instance) in destroyed.into_iter().rev() {
+            let Some(pre_destroy) = comp.pre_destroy else {
+                continue;
+            };
+            if let Err(err) = pre_destroy(instance).await {
+                eprintln!("[rspring] pre_destroy of {} failed: {}", comp.name, err);
+            }
+        }
+    }
+
+    // A child container for one request; singletons are shared with `self`
+    pub fn request_scope(self: &Arc<Self>) -> Arc<Self> {
+        let root = match &self.request {
+            Some(scope) => scope.root.clone(),
+            None => self.clone(),
+        };
+
+        Arc::new_cyclic(|this| Self {
+            components: HashMap::new(),
+            built: Mutex::new(Vec::new()),
+            durations: Mutex::new(HashMap::new()),
+            registered: Vec::new(),
+            candidates: HashMap::new(),
+            parent: None,
+            environment: root.environment.clone(),
+            conditions: Vec::new(),
+            request: Some(RequestScope {
+                root,
+                instances: Mutex::new(Vec::new()),
+            }),
+            this: this.clone(),
+            overridden: Vec::new(),
+        })
+    }
+
+    // Runs `f` with this request scope as the current one, so providers held
+    // by singletons resolve request-scoped components from it, then closes it
+    pub async fn scoped<F: Future>(self: Arc<Self>, f: F) -> F::Output {
+        let output = CURRENT_REQUEST.scope(self.clone(), f).await;
+        self.close().await;
+        output
     }
     
-    pub fn get<T: 'static + Send + Sync>(&self) -> Arc<T> {
-        self.components
-            .get(&TypeId::of::<T>())
-            .unwrap_or_else(|| panic!("{} not found in container", std::any::type_name::<T>()))
-            .clone()
-            .downcast::<T>()
+    pub fn get<T: ?Sized + 'static + Send + Sync>(&self) -> Arc<T> {
+        self.lookup::<T>(None)
+    }
+
+    // The component of type `T` registered under `name`
+    pub fn get_named<T: ?Sized + 'static + Send + Sync>(&self, name: &str) -> Arc<T> {
+        self.lookup::<T>(Some(name))
+    }
+
+    // Like `get`, but `None` when no single component of type `T` can be chosen
+    pub fn try_get<T: ?Sized + 'static + Send + Sync>(&self) -> Option<Arc<T>> {
+        self.try_lookup::<T>(None).ok()
+    }
+
+    pub fn try_get_named<T: ?Sized + 'static + Send + Sync>(&self, name: &str) -> Option<Arc<T>> {
+        self.try_lookup::<T>(Some(name)).ok()
+    }
+
+    // Every component of type `T`, sorted by #[order] and then by name
+    pub fn get_all<T: ?Sized + 'static + Send + Sync>(&self) -> Vec<Arc<T>> {
+        let type_id = TypeId::of::<T>();
+        let root = self.root();
+        let candidates = root.candidates.get(&type_id).map(Vec::as_slice).unwrap_or_default();
+        if let (true, Some(parent)) = (candidates.is_empty(), &root.parent) {
+            return parent.get_all::<T>();
+        }
+
+        candidates.iter()
+            .map(|comp| {
+                self.instance(comp, type_id)
+                    .downcast_ref::<Arc<T>>()
+                    .expect("Downcast failed")
+                    .clone()
+            })
+            .collect()
+    }
+
+    // A handle that looks `T` up when it is used rather than now
+    pub fn provider<T: ?Sized + 'static + Send + Sync>(&self) -> Provider<T> {
+        Provider {
+            container: self.this.clone(),
+            qualifier: None,
+            _type: PhantomData,
+        }
+    }
+
+    fn lookup<T: ?Sized + 'static + Send + Sync>(&self, qualifier: Option<&str>) -> Arc<T> {
+        let type_name = std::any::type_name::<T>();
+        match self.try_lookup::<T>(qualifier) {
+            Ok(instance) => instance,
+            Err(Unresolved::Missing) => panic!("{} not found in container", type_name),
+            Err(Unresolved::Ambiguous(names)) => {
+                panic!("{} is provided by {}; use get_named", type_name, names.join(", "))
+            }
+        }
+    }
+
+    fn try_lookup<T: ?Sized + 'static + Send + Sync>(&self, qualifier: Option<&str>) -> Result<Arc<T>, Unresolved> {
+        Ok(self.try_instance(TypeId::of::<T>(), qualifier)?
+            .downcast_ref::<Arc<T>>()
             .expect("Downcast failed")
+            .clone())
+    }
+
+    // The instance injected as `type_id`, holding an `Arc` of that type
+    fn try_instance(&self, type_id: TypeId, qualifier: Option<&str>) -> Result<Instance, Unresolved> {
+        let root = self.root();
+        let candidates = root.candidates.get(&type_id).map(Vec::as_slice).unwrap_or_default();
+        // Components of this container shadow those of the parent
+        if let (true, Some(parent)) = (candidates.is_empty(), &root.parent) {
+            return parent.try_instance(type_id, qualifier);
+        }
+        let comp = select(candidates, qualifier)?;
+        Ok(self.instance(comp, type_id))
+    }
+
+    fn is_registered(&self, type_id: TypeId) -> bool {
+        self.root().candidates.contains_key(&type_id)
+    }
+
+    // The components that can be injected as `type_id`, from the nearest container having any
+    fn candidates_for(&self, type_id: TypeId) -> &[&'static Component] {
+        let root = self.root();
+        match (root.candidates.get(&type_id), &root.parent) {
+            (Some(found), _) => found,
+            (None, Some(parent)) => parent.candidates_for(type_id),
+            (None, None) => &[],
+        }
+    }
+
+    // The components of this container and of its ancestors
+    fn visible_components(&self) -> Vec<&'static Component> {
+        let root = self.root();
+        let mut components = root.registered.clone();
+        if let Some(parent) = &root.parent {
+            components.extend(parent.visible_components());
+        }
+        components
+    }
+
+    fn root(&self) -> &ServiceContainer {
+        match &self.request {
+            Some(scope) => &scope.root,
+            None => self,
+        }
+    }
+
+    fn instance(&self, comp: &'static Component, type_id: TypeId) -> Instance {
+        // Injected as a trait object: resolve the component itself, then cast
+        if type_id != comp.type_id {
+            let provided = comp.provides.iter().find(|p| p.type_id == type_id).unwrap();
+            return (provided.cast)(self.instance(comp, comp.type_id));
+        }
+
+        match comp.scope {
+            Scope::Singleton => {
+                // Lazy singletons are built once, by whichever caller gets here first
+                let root = self.root();
+                root.components[&type_id]
+                    .get_or_init(|| {
+                        let started = Instant::now();
+                        let instance = root.construct(comp);
+                        let elapsed = started.elapsed();
+                        println!("[rspring]   ✓ {} (lazy, {:.2?})", comp.name, elapsed);
+                        root.built.lock().unwrap().push(comp);
+                        root.durations.lock().unwrap().insert(comp.type_id, elapsed);
+                        instance
+                    })
+                    .clone()
+            }
+            Scope::Prototype => self.construct(comp),
+            Scope::Request => {
+                let scope = self.request.as_ref().unwrap_or_else(|| {
+                    panic!("{} is request-scoped and can only be resolved inside a request", comp.name)
+                });
+                let existing = |instances: &[(&Component, Instance)]| {
+                    instances.iter().find(|(c, _)| c.type_id == type_id).map(|(_, i)| i.clone())
+                };
+                if let Some(instance) = existing(&scope.instances.lock().unwrap()) {
+                    return instance;
+                }
+                // Build without holding the lock, dependencies may be request-scoped too
+                let instance = self.construct(comp);
+                let mut instances = scope.instances.lock().unwrap();
+                existing(&instances).unwrap_or_else(|| {
+                    instances.push((comp, instance.clone()));
+                    instance
+                })
+            }
+        }
+    }
+
+    // Build a component on demand, outside of startup
+    fn construct(&self, comp: &'static Component) -> Instance {
+        let built = block_on(async {
+            let instance = (comp.build)(self).await?;
+            if let Some(post_construct) = comp.post_construct {
+                post_construct(instance.clone()).await?;
+            }
+            Ok::<_, BuildError>(instance)
+        });
+        built
+            .unwrap_or_else(|| panic!("{} is built asynchronously, which cannot be awaited on a current-thread tokio runtime", comp.name))
+            .unwrap_or_else(|err| panic!("failed to construct {}: {}", comp.name, err))
     }
 }
 
+// Drive a constructor to completion from synchronous code. Outside a tokio runtime
+// one is started for it. Inside one, sync constructors are ready on the first poll
+// and async ones block the thread, which only the multi-threaded flavour allows:
+// on a current-thread runtime, `None` is returned instead.
+fn block_on<F: Future>(future: F) -> Option<F::Output> {
+    let Ok(handle) = tokio::runtime::Handle::try_current() else {
+        let runtime = tokio::runtime::Builder::new_current_thread().enable_all().build().unwrap();
+        return Some(runtime.block_on(future));
+    };
+
+    let mut future = std::pin::pin!(future);
+    if let Poll::Ready(output) = future.as_mut().poll(&mut Context::from_waker(Waker::noop())) {
+        return Some(output);
+    }
+    match handle.runtime_flavor() {
+        RuntimeFlavor::CurrentThread => None,
+        _ => Some(tokio::task::block_in_place(|| handle.block_on(future))),
+    }
+}
+
+fn on_current_thread_runtime() -> bool {
+    tokio::runtime::Handle::try_current().is_ok_and(|handle| handle.runtime_flavor() == RuntimeFlavor::CurrentThread)
+}
+
+// Deferred lookup of a component, injected for `Provider<T>` constructor arguments.
+// It adds no build-order edge, so it can break a dependency cycle, and lets a
+// singleton reach request-scoped components of the request being handled.
+pub struct Provider<T: ?Sized> {
+    container: Weak<ServiceContainer>,
+    qualifier: Option<&'static str>,
+    _type: PhantomData<fn() -> Arc<T>>,
+}
+
+impl<T: ?Sized + 'static + Send + Sync> Provider<T> {
+    // Look up the component registered under `name` instead
+    pub fn named(mut self, name: &'static str) -> Self {
+        self.qualifier = Some(name);
+        self
+    }
+
+    pub fn get(&self) -> Arc<T> {
+        self.container().lookup::<T>(self.qualifier)
+    }
+
+    pub fn try_get(&self) -> Option<Arc<T>> {
+        self.container().try_lookup::<T>(self.qualifier).ok()
+    }
+
+    fn container(&self) -> Arc<ServiceContainer> {
+        current(&self.container)
+    }
+}
+
+// The request scope being handled if it belongs to `container`, otherwise `container`
+fn current(container: &Weak<ServiceContainer>) -> Arc<ServiceContainer> {
+    let container = container.upgrade().expect("ServiceContainer has been dropped");
+    CURRENT_REQUEST.try_with(Arc::clone).ok()
+        .filter(|current| std::ptr::eq(current.root(), container.root()))
+        .unwrap_or(container)
+}
+
+enum Unresolved {
+    Missing,
+    Ambiguous(Vec<&'static str>),
+}
+
+// Picks the component to inject: the one named by the qualifier,
+// the only candidate, or the primary one among several
+fn select<'a>(candidates: &[&'a Component], qualifier: Option<&str>) -> Result<&'a Component, Unresolved> {
+    if let Some(name) = qualifier {
+        return candidates.iter().find(|c| c.name == name).copied().ok_or(Unresolved::Missing);
+    }
+
+    match candidates {
+        [] => Err(Unresolved::Missing),
+        [only] => Ok(only),
+        _ => match candidates.iter().filter(|c| c.primary).collect::<Vec<_>>().as_slice() {
+            [primary] => Ok(primary),
+            _ => Err(Unresolved::Ambiguous(candidates.iter().map(|c| c.name).collect())),
+        },
+    }
+}
+
+// Index components by every type they can be injected as, rejecting two
+// components with the same concrete type or the same name
+fn index(components: &[&'static Component]) -> Result<HashMap<TypeId, Vec<&'static Component>>, ContainerError> {
+    let mut by_name: HashMap<&str, &Component> = HashMap::new();
+    let mut by_type: HashMap<TypeId, &Component> = HashMap::new();
+    let mut candidates: HashMap<TypeId, Vec<&'static Component>> = HashMap::new();
+
+    for &comp in components {
+        let same_name = by_name.insert(comp.name, comp);
+        let same_type = by_type.insert(comp.type_id, comp);
+        if let Some(existing) = same_name.or(same_type) {
+            return Err(ContainerError::DuplicateComponent {
+                component: comp.name,
+                existing: existing.name,
+            });
+        }
+
+        candidates.entry(comp.type_id).or_default().push(comp);
+        for provided in comp.provides {
+            candidates.entry(provided.type_id).or_default().push(comp);
+        }
+    }
+
+    for found in candidates.values_mut() {
+        found.sort_by_key(|comp| (comp.order, comp.name));
+    }
+
+    Ok(candidates)
+}
+
+// The components of `roots` and those of this container they need to be built,
+// directly or not; providers are looked up later, so they are left out
+fn with_dependencies(
+    roots: Vec<&'static Component>,
+    candidates: &HashMap<TypeId, Vec<&'static Component>>,
+) -> HashSet<TypeId> {
+    let mut found = HashSet::new();
+    let mut pending = roots;
+    while let Some(comp) = pending.pop() {
+        if !found.insert(comp.type_id) {
+            continue;
+        }
+        for dep in comp.dependencies {
+            let targets = candidates.get(&dep.type_id).map(Vec::as_slice).unwrap_or_default();
+            match dep.kind {
+                DependencyKind::All => pending.extend(targets),
+                DependencyKind::Provider => {}
+                DependencyKind::One | DependencyKind::Optional => pending.extend(select(targets, dep.qualifier).ok()),
+            }
+        }
+    }
+    found
+}
+
+// Group components into layers (Kahn's algorithm): every dependency sits in an
+// earlier layer than its dependents, and each layer is sorted by name so the order
+// does not depend on link order. Reports missing or ambiguous dependencies, scope
+// violations and the exact chain of any cycle.
+fn build_order(
+    components: &[&'static Component],
+    candidates: &HashMap<TypeId, Vec<&'static Component>>,
+    parent: Option<&ServiceContainer>,
+) -> Result<Vec<Vec<&'static Component>>, ContainerError> {
+    // The components of this container chosen for every dependency of every component.
+    // Those resolved from the parent are already built, so they add no edge.
+    let mut resolved: HashMap<TypeId, Vec<&'static Component>> = HashMap::new();
+    for comp in components {
+        let mut deps = Vec::with_capacity(comp.dependencies.len());
+        for dep in comp.dependencies {
+            let (found, inherited) = match (candidates.get(&dep.type_id), parent) {
+                (Some(found), _) => (found.as_slice(), false),
+                (None, Some(parent)) => (parent.candidates_for(dep.type_id), true),
+                (None, None) => (&[][..], false),
+            };
+            let targets = match dep.kind {
+                // A collection waits for all of its contributors, and may be empty
+                DependencyKind::All => found.to_vec(),
+                // Looked up on use, so no ordering constraint
+                DependencyKind::Provider => continue,
+                DependencyKind::One | DependencyKind::Optional => match select(found, dep.qualifier) {
+                    Ok(target) => vec![target],
+                    Err(Unresolved::Missing) if dep.kind == DependencyKind::Optional => continue,
+                    Err(Unresolved::Missing) => return Err(ContainerError::MissingDependency {
+                        component: comp.name,
+                        dependency: (dep.type_name)(),
+                        qualifier: dep.qualifier,
+                    }),
+                    Err(Unresolved::Ambiguous(names)) => return Err(ContainerError::AmbiguousDependency {
+                        component: comp.name,
+                        dependency: (dep.type_name)(),
+                        candidates: names,
+                    }),
+                },
+            };
+            if !inherited {
+                deps.extend(targets);
+                continue;
+            }
+            let request_scoped = targets.iter().find(|target| target.scope == Scope::Request);
+            if let (Scope::Singleton, Some(target)) = (comp.scope, request_scoped) {
+                return Err(ContainerError::ScopeMismatch {
+                    component: comp.name,
+                    dependency: target.name,
+                });
+            }
+        }
+        resolved.insert(comp.type_id, deps);
+    }
+
+    // How many dependencies each component still waits for, and who waits on it
+    let mut waiting: HashMap<TypeId, usize> = HashMap::new();
+    let mut dependents: HashMap<TypeId, Vec<&'static Component>> = HashMap::new();
+    for comp in components {
+        let deps = &resolved[&comp.type_id];
+        waiting.insert(comp.type_id, deps.len());
+        for dep in deps {
+            dependents.entry(dep.type_id).or_default().push(comp);
+        }
+    }
+
+    let mut layers: Vec<Vec<&'static Component>> = Vec::new();
+    let mut layer: Vec<_> = components.iter().copied().filter(|comp| waiting[&comp.type_id] == 0).collect();
+    while !layer.is_empty() {
+        layer.sort_by_key(|comp| comp.name);
+        let mut next = Vec::new();
+        for comp in &layer {
+            for &dependent in dependents.get(&comp.type_id).map(Vec::as_slice).unwrap_or_default() {
+                let count = waiting.get_mut(&dependent.type_id).unwrap();
+                *count -= 1;
+                if *count == 0 {
+                    next.push(dependent);
+                }
+            }
+        }
+        layers.push(std::mem::replace(&mut layer, next));
+    }
+
+    // Whatever was never ready is on a cycle or depends on one. Each of those still
+    // waits for another, so following them from the first by name must loop.
+    let stuck = components.iter().copied().filter(|comp| waiting[&comp.type_id] > 0);
+    if let Some(first) = stuck.min_by_key(|comp| comp.name) {
+        let mut path = vec![first];
+        let mut current = first;
+        loop {
+            current = resolved[&current.type_id].iter()
+                .copied()
+                .find(|dep| waiting[&dep.type_id] > 0)
+                .expect("a component left waiting depends on another one left waiting");
+            if let Some(start) = path.iter().position(|c| c.type_id == current.type_id) {
+                let mut path: Vec<_> = path[start..].iter().map(|c| c.name).collect();
+                path.push(current.name);
+                return Err(ContainerError::CircularDependency { path });
+            }
+            path.push(current);
+        }
+    }
+
+    // A prototype built from request-scoped parts is tied to the request as well
+    let mut request_bound: HashMap<TypeId, bool> = HashMap::new();
+    for comp in layers.iter().flatten() {
+        let bound_dep = resolved[&comp.type_id].iter().find(|dep| request_bound[&dep.type_id]);
+        let bound = match comp.scope {
+            Scope::Request => true,
+            Scope::Prototype => bound_dep.is_some(),
+            Scope::Singleton => {
+                if let Some(dep) = bound_dep {
+                    return Err(ContainerError::ScopeMismatch {
+                        component: comp.name,
+                        dependency: dep.name,
+                    });
+                }
+                false
+            }
+        };
+        request_bound.insert(comp.type_id, bound);
+    }
+
+    Ok(layers)
+}
+
 
 pub struct Application {
     addr: String,
+    environment: Environment,
 }
 
 mod runtime {
     use axum::Router;
     use tokio::net::TcpListener;
-    use crate::{inventory, Route, ServiceContainer};
+    use crate::{inventory, ContainerError, Environment, Route, Scheduler, ServiceContainer, TaskExecutors};
+    use crate::{ApplicationReady, ApplicationStarting, ContextClosing, ContextRefreshed};
     use axum::Extension;
     
-    pub fn run(addr: String) {
+    pub fn run(addr: String, environment: Environment) -> Result<(), ContainerError> {
         let rt = tokio::runtime::Runtime::new().unwrap();
 
         rt.block_on(async move {
             // Build all components
-            let container = ServiceContainer::build();
+            let container = ServiceContainer::builder()
+                .environment(environment)
+                .starting(ApplicationStarting { addr: addr.clone() })
+                .try_build_async()
+                .await?;
             
             let mut router = Router::new();
 
             for route in inventory::iter::<Route> {
+                if route.controller.is_some_and(|controller| !container.is_registered(controller)) {
+                    continue;
+                }
                 println!("[rspring] {:#?} {}", route.method, route.path);
                 router = route.add_to_router(router);
             }
 
             // Add the container as an extension so handlers can access it
-            router = router.layer(Extension(container));
+            router = router.layer(Extension(container.clone()));
+            container.publish_event(ContextRefreshed).await;
+            let scheduler = match Scheduler::start(&container) {
+                Ok(scheduler) => scheduler,
+                Err(err) => {
+                    // The singletons are built by now, let them clean up
+                    container.close().await;
+                    return Err(err);
+                }
+            };
 
             let listener = TcpListener::bind(&addr).await.unwrap();
-            println!("[rspring] Server running on http://{}", addr);
-            axum::serve(listener, router).await.unwrap();
-        });
+            let local_addr = listener.local_addr().unwrap();
+            println!("[rspring] Server running on http://{}", local_addr);
+            container.publish_event(ApplicationReady { local_addr }).await;
+
+            let closing = container.clone();
+            axum::serve(listener, router)
+                .with_graceful_shutdown(async move {
+                    shutdown_signal().await;
+                    println!("[rspring] Shutting down...");
+                    closing.publish_event(ContextClosing).await;
+                })
+                .await
+                .unwrap();
+
+            scheduler.shutdown().await;
+            if let Some(executors) = container.try_get::<TaskExecutors>() {
+                executors.shutdown().await;
+            }
+            container.close().await;
+            Ok(())
+        })
+    }
+
+    // Ctrl-C, or SIGTERM on unix
+    async fn shutdown_signal() {
+        let ctrl_c = async {
+            tokio::signal::ctrl_c().await.unwrap();
+        };
+
+        #[cfg(unix)]
+        let terminate = async {
+            tokio::signal::unix::signal(tokio::signal::unix::SignalKind::terminate())
+                .unwrap()
+                .recv()
+                .await;
+        };
+        #[cfg(not(unix))]
+        let terminate = std::future::pending::<()>();
+
+        tokio::select! {
+            _ = ctrl_c => {},
+            _ = terminate => {},
+        }
     }
 }
 
@@ -142,6 +1180,7 @@ impl Application {
     pub fn new() -> Self {
         Self {
             addr: "127.0.0.1:3000".into(),
+            environment: Environment::load(),
         }
     }
 
@@ -150,7 +1189,254 @@ impl Application {
         self
     }
 
+    // Set a property, taking precedence over environment variables and application.properties
+    pub fn property(mut self, key: &str, value: &str) -> Self {
+        self.environment.set(key, value);
+        self
+    }
+
     pub fn run(self) {
-        runtime::run(self.addr);
+        if let Err(err) = runtime::run(self.addr, self.environment) {
+            eprintln!("[rspring] Application failed to start: {}", err);
+            std::process::exit(1);
+        }
+    }
+}
+
+impl Default for Application {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use test_support::component;
+
+    // Built by every container of these tests, with a constructor that awaits a timer
+    struct SlowComponent;
+
+    inventory::submit! {
+        Component {
+            build: |_| Box::pin(async {
+                tokio::time::sleep(Duration::from_millis(1)).await;
+                Ok(Arc::new(Arc::new(SlowComponent)) as Instance)
+            }),
+            ..component::<SlowComponent>("SlowComponent", &[])
+        }
+    }
+
+    #[test]
+    fn try_build_awaits_async_constructors_outside_a_runtime() {
+        let container = ServiceContainer::builder().environment(Environment::new()).try_build().unwrap();
+        assert!(container.try_get::<SlowComponent>().is_some());
+    }
+
+    #[test]
+    fn try_build_blocks_on_a_multi_threaded_runtime() {
+        let runtime = tokio::runtime::Builder::new_multi_thread().enable_all().build().unwrap();
+        runtime.block_on(async {
+            let container = ServiceContainer::builder().environment(Environment::new()).try_build().unwrap();
+            assert!(container.try_get::<SlowComponent>().is_some());
+        });
+    }
+
+    #[tokio::test]
+    async fn try_build_fails_on_a_current_thread_runtime() {
+        let result = ServiceContainer::builder().environment(Environment::new()).try_build();
+        assert!(matches!(result, Err(ContainerError::BlockingBuild)));
+
+        let container = ServiceContainer::builder().environment(Environment::new()).try_build_async().await.unwrap();
+        assert!(container.try_get::<SlowComponent>().is_some());
+    }
+
+    #[tokio::test]
+    async fn block_on_only_returns_ready_futures_on_a_current_thread_runtime() {
+        assert_eq!(block_on(async { 1 }), Some(1));
+        assert_eq!(block_on(tokio::time::sleep(Duration::from_millis(1))), None);
+    }
+
+    trait Store: Send + Sync {}
+    struct A;
+    struct B;
+    struct C;
+    struct D;
+    struct E;
+    struct Unregistered;
+
+    const STORE: &[ProvidedType] = &[ProvidedType {
+        type_id: TypeId::of::<dyn Store>(),
+        type_name: std::any::type_name::<dyn Store>,
+        cast: |instance| instance,
+    }];
+
+    fn order(components: &[&'static Component]) -> Result<Vec<Vec<&'static str>>, ContainerError> {
+        let candidates = index(components)?;
+        let layers = build_order(components, &candidates, None)?;
+        Ok(layers.iter().map(|layer| layer.iter().map(|comp| comp.name).collect()).collect())
+    }
+
+    #[test]
+    fn layers_are_sorted_by_name_whatever_the_registration_order() {
+        static A_: Component = component::<A>("A", &[Dependency::of::<B>()]);
+        static B_: Component = component::<B>("B", &[]);
+        static C_: Component = component::<C>("C", &[Dependency::of::<B>(), Dependency::of::<D>()]);
+        static D_: Component = component::<D>("D", &[]);
+        static E_: Component = component::<E>("E", &[Dependency::of::<C>()]);
+
+        let expected = vec![vec!["B", "D"], vec!["A", "C"], vec!["E"]];
+        assert_eq!(order(&[&E_, &D_, &C_, &B_, &A_]).unwrap(), expected);
+        assert_eq!(order(&[&A_, &C_, &E_, &B_, &D_]).unwrap(), expected);
+    }
+
+    #[test]
+    fn cycle_reports_the_exact_path() {
+        static A_: Component = component::<A>("A", &[Dependency::of::<B>()]);
+        static B_: Component = component::<B>("B", &[Dependency::of::<C>()]);
+        static C_: Component = component::<C>("C", &[Dependency::of::<A>()]);
+        static D_: Component = component::<D>("D", &[]);
+
+        match order(&[&C_, &D_, &B_, &A_]) {
+            Err(ContainerError::CircularDependency { path }) => assert_eq!(path, ["A", "B", "C", "A"]),
+            other => panic!("expected a cycle, got {:?}", other),
+        }
+    }
+
+    #[test]
+    fn cycle_path_leaves_out_the_components_leading_to_it() {
+        static A_: Component = component::<A>("A", &[Dependency::of::<B>()]);
+        static B_: Component = component::<B>("B", &[Dependency::of::<C>()]);
+        static C_: Component = component::<C>("C", &[Dependency::of::<D>()]);
+        static D_: Component = component::<D>("D", &[Dependency::of::<B>()]);
+
+        let err = order(&[&A_, &B_, &C_, &D_]).unwrap_err();
+        assert_eq!(err.to_string(), "circular dependency: B -> C -> D -> B");
+    }
+
+    #[test]
+    fn provider_breaks_a_cycle() {
+        static A_: Component = component::<A>("A", &[Dependency::of::<B>()]);
+        static B_: Component = component::<B>("B", &[Dependency::provider::<A>()]);
+
+        assert_eq!(order(&[&A_, &B_]).unwrap(), vec![vec!["B"], vec!["A"]]);
+    }
+
+    #[test]
+    fn missing_dependency() {
+        static A_: Component = component::<A>("A", &[Dependency::of::<Unregistered>()]);
+        static B_: Component = component::<B>("B", &[Dependency::of::<Unregistered>().named("unregistered")]);
+        static C_: Component = component::<C>("C", &[Dependency::optional::<Unregistered>()]);
+
+        match order(&[&A_]) {
+            Err(ContainerError::MissingDependency { component: "A", dependency, qualifier: None }) => {
+                assert_eq!(dependency, std::any::type_name::<Unregistered>());
+            }
+            other => panic!("expected a missing dependency, got {:?}", other),
+        }
+        assert!(matches!(
+            order(&[&B_]),
+            Err(ContainerError::MissingDependency { component: "B", qualifier: Some("unregistered"), .. })
+        ));
+        assert_eq!(order(&[&C_]).unwrap(), vec![vec!["C"]]);
+    }
+
+    #[test]
+    fn ambiguous_dependency_unless_one_is_primary_or_named() {
+        static A_: Component = component::<A>("A", &[Dependency::of::<dyn Store>()]);
+        static B_: Component = Component { provides: STORE, ..component::<B>("B", &[]) };
+        static C_: Component = Component { provides: STORE, ..component::<C>("C", &[]) };
+        static PRIMARY_C: Component = Component { primary: true, ..C_ };
+        static NAMED: Component = component::<A>("A", &[Dependency::of::<dyn Store>().named("B")]);
+
+        match order(&[&A_, &B_, &C_]) {
+            Err(ContainerError::AmbiguousDependency { component: "A", candidates, .. }) => {
+                assert_eq!(candidates, ["B", "C"]);
+            }
+            other => panic!("expected an ambiguous dependency, got {:?}", other),
+        }
+        assert_eq!(order(&[&A_, &B_, &PRIMARY_C]).unwrap(), vec![vec!["B", "C"], vec!["A"]]);
+        assert_eq!(order(&[&NAMED, &B_, &C_]).unwrap(), vec![vec!["B", "C"], vec!["A"]]);
+    }
+
+    #[test]
+    fn duplicate_component() {
+        static A_: Component = component::<A>("Same", &[]);
+        static B_: Component = component::<B>("Same", &[]);
+        static OTHER_A: Component = component::<A>("Other", &[]);
+
+        assert!(matches!(
+            index(&[&A_, &B_]),
+            Err(ContainerError::DuplicateComponent { component: "Same", existing: "Same" })
+        ));
+        assert!(matches!(
+            index(&[&A_, &OTHER_A]),
+            Err(ContainerError::DuplicateComponent { component: "Other", existing: "Same" })
+        ));
+    }
+
+    #[test]
+    fn singleton_cannot_depend_on_a_request_scoped_component() {
+        static A_: Component = component::<A>("A", &[Dependency::of::<B>()]);
+        static B_: Component = Component { scope: Scope::Request, ..component::<B>("B", &[]) };
+        // Through a prototype built from it
+        static C_: Component = component::<C>("C", &[Dependency::of::<D>()]);
+        static D_: Component = Component { scope: Scope::Prototype, ..component::<D>("D", &[Dependency::of::<B>()]) };
+        static E_: Component = Component { scope: Scope::Request, ..component::<E>("E", &[Dependency::of::<D>()]) };
+
+        assert!(matches!(order(&[&A_, &B_]), Err(ContainerError::ScopeMismatch { component: "A", dependency: "B" })));
+        assert!(matches!(
+            order(&[&B_, &C_, &D_]),
+            Err(ContainerError::ScopeMismatch { component: "C", dependency: "D" })
+        ));
+        assert_eq!(order(&[&B_, &D_, &E_]).unwrap(), vec![vec!["B"], vec!["D"], vec!["E"]]);
+    }
+
+    #[tokio::test]
+    async fn construction_failed_keeps_the_constructor_error() {
+        static A_: Component = component::<A>("A", &[]);
+
+        let result = ServiceContainer::assemble(Environment::new(), vec![&A_], Vec::new(), None, None).await;
+        let Err(err) = result else {
+            panic!("expected the construction to fail");
+        };
+        assert!(matches!(err, ContainerError::ConstructionFailed { component: "A", .. }));
+        assert_eq!(err.to_string(), "failed to construct A: never built");
+        assert_eq!(std::error::Error::source(&err).unwrap().to_string(), "never built");
+    }
+
+    // Listens to ApplicationStarting in the test below, and needs an A to be built
+    struct StartingListener;
+
+    inventory::submit! {
+        EventListener {
+            component: TypeId::of::<StartingListener>(),
+            name: "StartingListener::on_starting",
+            event: TypeId::of::<ApplicationStarting>(),
+            order: 0,
+            background: false,
+            condition: None,
+            handle: |container, _| Box::pin(async move {
+                container.get::<StartingListener>();
+                Ok(())
+            }),
+        }
+    }
+
+    #[tokio::test]
+    async fn starting_listeners_that_cannot_be_built_fail_the_build() {
+        static A_: Component = component::<A>("A", &[]);
+        static LISTENER: Component = component::<StartingListener>("StartingListener", &[Dependency::of::<A>()]);
+        let publisher = inventory::iter::<Component>()
+            .find(|comp| comp.type_id == TypeId::of::<EventPublisher>())
+            .unwrap();
+
+        let starting = ApplicationStarting { addr: "127.0.0.1:0".to_string() };
+        let components = vec![publisher, &LISTENER, &A_];
+        let result = ServiceContainer::assemble(Environment::new(), components, Vec::new(), None, Some(starting)).await;
+        let Err(err) = result else {
+            panic!("expected the construction to fail");
+        };
+        assert!(matches!(err, ContainerError::ConstructionFailed { component: "A", .. }), "{}", err);
     }
 }
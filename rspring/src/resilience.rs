@@ -0,0 +1,408 @@
+use crate::{Component, Instance};
+use std::collections::{HashMap, VecDeque};
+use std::fmt;
+use std::future::Future;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+
+// How #[retryable] calls a method again after an `Err`
+#[derive(Debug, Clone, Copy)]
+pub struct RetryPolicy {
+    // Including the first call
+    pub max_attempts: u32,
+    pub backoff: Backoff,
+    // Before the second attempt
+    pub delay: Duration,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Backoff {
+    Fixed,
+    // The delay doubles after each attempt
+    Exponential,
+}
+
+impl RetryPolicy {
+    // Calls `call` until it succeeds, `retry_on` rejects its error or the attempts
+    // run out, returning the last result
+    pub async fn run<T, E, F, Fut>(self, name: &str, mut call: F, retry_on: fn(&E) -> bool) -> Result<T, E>
+    where
+        F: FnMut() -> Fut,
+        Fut: Future<Output = Result<T, E>>,
+        E: fmt::Display,
+    {
+        let mut delay = self.delay;
+        let mut attempt = 1;
+        loop {
+            let err = match call().await {
+                Ok(value) => return Ok(value),
+                Err(err) => err,
+            };
+            if attempt >= self.max_attempts || !retry_on(&err) {
+                return Err(err);
+            }
+            eprintln!("[rspring] {} failed (attempt {}/{}): {}, retrying in {:?}", name, attempt, self.max_attempts, err, delay);
+            tokio::time::sleep(delay).await;
+            if self.backoff == Backoff::Exponential {
+                delay *= 2;
+            }
+            attempt += 1;
+        }
+    }
+}
+
+// Set by the first #[circuit_breaker] using a name; later ones share the breaker
+#[derive(Debug, Clone, Copy)]
+pub struct CircuitBreakerConfig {
+    // Percentage of failed calls in the window that opens the circuit
+    pub failure_rate: u8,
+    // How long the circuit stays open before letting a trial call through
+    pub wait: Duration,
+    // The number of latest calls the failure rate is computed over, once that many were made
+    pub window: usize,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CircuitState {
+    Closed,
+    // Calls are rejected until `wait` has passed
+    Open,
+    // One trial call decides whether to close or open again
+    HalfOpen,
+}
+
+impl fmt::Display for CircuitState {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            CircuitState::Closed => "closed",
+            CircuitState::Open => "open",
+            CircuitState::HalfOpen => "half-open",
+        })
+    }
+}
+
+// Counters since the breaker was created, and the failure rate of the current window
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub struct CircuitMetrics {
+    pub successful_calls: u64,
+    pub failed_calls: u64,
+    pub rejected_calls: u64,
+    // Percentage, `None` until the window is full
+    pub failure_rate: Option<f32>,
+}
+
+// Returned instead of calling a method while its circuit is open; methods without
+// a fallback need an error type implementing `From<CallNotPermitted>`
+#[derive(Debug, Clone)]
+pub struct CallNotPermitted {
+    pub name: String,
+}
+
+impl fmt::Display for CallNotPermitted {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "circuit breaker {} is open", self.name)
+    }
+}
+
+impl std::error::Error for CallNotPermitted {}
+
+pub struct CircuitBreaker {
+    name: String,
+    config: CircuitBreakerConfig,
+    state: Mutex<BreakerState>,
+}
+
+struct BreakerState {
+    state: CircuitState,
+    // Outcomes of the latest calls while closed, `true` for failures
+    outcomes: VecDeque<bool>,
+    opened_at: Option<Instant>,
+    trial_running: bool,
+    metrics: CircuitMetrics,
+}
+
+impl CircuitBreaker {
+    fn new(name: &str, config: CircuitBreakerConfig) -> Self {
+        Self {
+            name: name.to_string(),
+            config,
+            state: Mutex::new(BreakerState {
+                state: CircuitState::Closed,
+                outcomes: VecDeque::new(),
+                opened_at: None,
+                trial_running: false,
+                metrics: CircuitMetrics::default(),
+            }),
+        }
+    }
+
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    pub fn config(&self) -> CircuitBreakerConfig {
+        self.config
+    }
+
+    pub fn state(&self) -> CircuitState {
+        let mut state = self.state.lock().unwrap();
+        self.refresh(&mut state);
+        state.state
+    }
+
+    pub fn metrics(&self) -> CircuitMetrics {
+        let state = self.state.lock().unwrap();
+        let mut metrics = state.metrics;
+        if state.outcomes.len() >= self.config.window {
+            let failures = state.outcomes.iter().filter(|&&failed| failed).count();
+            metrics.failure_rate = Some(failures as f32 * 100.0 / state.outcomes.len() as f32);
+        }
+        metrics
+    }
+
+    // Runs `call` if the circuit lets it through and records whether it failed.
+    // A call dropped before finishing counts as failed.
+    pub async fn call<T, E, Fut>(&self, call: Fut) -> Result<Result<T, E>, CallNotPermitted>
+    where
+        Fut: Future<Output = Result<T, E>>,
+    {
+        self.acquire()?;
+        let mut outcome = Outcome { breaker: self, failed: true };
+        let result = call.await;
+        outcome.failed = result.is_err();
+        Ok(result)
+    }
+
+    fn acquire(&self) -> Result<(), CallNotPermitted> {
+        let mut state = self.state.lock().unwrap();
+        self.refresh(&mut state);
+        let permitted = match state.state {
+            CircuitState::Closed => true,
+            CircuitState::Open => false,
+            CircuitState::HalfOpen => !std::mem::replace(&mut state.trial_running, true),
+        };
+        if !permitted {
+            state.metrics.rejected_calls += 1;
+            return Err(CallNotPermitted { name: self.name.clone() });
+        }
+        Ok(())
+    }
+
+    fn record(&self, failed: bool) {
+        let mut state = self.state.lock().unwrap();
+        if failed {
+            state.metrics.failed_calls += 1;
+        } else {
+            state.metrics.successful_calls += 1;
+        }
+        match state.state {
+            CircuitState::HalfOpen => {
+                state.trial_running = false;
+                self.transition(&mut state, if failed { CircuitState::Open } else { CircuitState::Closed });
+            }
+            CircuitState::Closed => {
+                state.outcomes.push_back(failed);
+                if state.outcomes.len() > self.config.window {
+                    state.outcomes.pop_front();
+                }
+                let failures = state.outcomes.iter().filter(|&&failed| failed).count();
+                if state.outcomes.len() >= self.config.window
+                    && failures * 100 >= state.outcomes.len() * self.config.failure_rate as usize
+                {
+                    self.transition(&mut state, CircuitState::Open);
+                }
+            }
+            // Let through before the circuit opened
+            CircuitState::Open => {}
+        }
+    }
+
+    // An open circuit becomes half-open once `wait` has passed
+    fn refresh(&self, state: &mut BreakerState) {
+        let waited = state.opened_at.is_some_and(|opened_at| opened_at.elapsed() >= self.config.wait);
+        if state.state == CircuitState::Open && waited {
+            self.transition(state, CircuitState::HalfOpen);
+        }
+    }
+
+    fn transition(&self, state: &mut BreakerState, to: CircuitState) {
+        state.state = to;
+        state.outcomes.clear();
+        state.opened_at = (to == CircuitState::Open).then(Instant::now);
+        println!("[rspring] Circuit breaker {} is {}", self.name, to);
+    }
+}
+
+// Records the outcome of a call when it finishes or is dropped
+struct Outcome<'a> {
+    breaker: &'a CircuitBreaker,
+    failed: bool,
+}
+
+impl Drop for Outcome<'_> {
+    fn drop(&mut self) {
+        self.breaker.record(self.failed);
+    }
+}
+
+// Injectable as `Arc<CircuitBreakerRegistry>`; holds the breakers of every
+// #[circuit_breaker], by name, for monitoring
+pub struct CircuitBreakerRegistry {
+    breakers: Mutex<HashMap<String, Arc<CircuitBreaker>>>,
+}
+
+impl CircuitBreakerRegistry {
+    // The breaker named `name`, created with `config` if it does not exist yet
+    pub fn breaker(&self, name: &str, config: CircuitBreakerConfig) -> Arc<CircuitBreaker> {
+        self.breakers.lock().unwrap()
+            .entry(name.to_string())
+            .or_insert_with(|| Arc::new(CircuitBreaker::new(name, config)))
+            .clone()
+    }
+
+    pub fn get(&self, name: &str) -> Option<Arc<CircuitBreaker>> {
+        self.breakers.lock().unwrap().get(name).cloned()
+    }
+
+    // Sorted by name
+    pub fn breakers(&self) -> Vec<Arc<CircuitBreaker>> {
+        let mut breakers: Vec<_> = self.breakers.lock().unwrap().values().cloned().collect();
+        breakers.sort_by(|a, b| a.name.cmp(&b.name));
+        breakers
+    }
+}
+
+inventory::submit! {
+    Component::framework::<CircuitBreakerRegistry>("CircuitBreakerRegistry", |_| {
+        let registry = CircuitBreakerRegistry { breakers: Mutex::new(HashMap::new()) };
+        Box::pin(async move { Ok(Arc::new(Arc::new(registry)) as Instance) })
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn breaker(failure_rate: u8, window: usize) -> CircuitBreaker {
+        CircuitBreaker::new("inventory", CircuitBreakerConfig { failure_rate, wait: Duration::from_millis(20), window })
+    }
+
+    async fn succeed(breaker: &CircuitBreaker) -> Result<Result<(), ()>, CallNotPermitted> {
+        breaker.call(async { Ok(()) }).await
+    }
+
+    async fn fail(breaker: &CircuitBreaker) -> Result<Result<(), ()>, CallNotPermitted> {
+        breaker.call(async { Err(()) }).await
+    }
+
+    #[tokio::test]
+    async fn opens_then_closes_after_a_successful_trial() {
+        let breaker = breaker(50, 2);
+        succeed(&breaker).await.unwrap().unwrap();
+        fail(&breaker).await.unwrap().unwrap_err();
+        assert_eq!(breaker.state(), CircuitState::Open);
+        assert!(succeed(&breaker).await.is_err());
+
+        tokio::time::sleep(Duration::from_millis(30)).await;
+        assert_eq!(breaker.state(), CircuitState::HalfOpen);
+        succeed(&breaker).await.unwrap().unwrap();
+        assert_eq!(breaker.state(), CircuitState::Closed);
+        assert_eq!(breaker.metrics(), CircuitMetrics {
+            successful_calls: 2,
+            failed_calls: 1,
+            rejected_calls: 1,
+            failure_rate: None,
+        });
+    }
+
+    #[tokio::test]
+    async fn a_failed_trial_opens_again() {
+        let breaker = breaker(100, 1);
+        fail(&breaker).await.unwrap().unwrap_err();
+        tokio::time::sleep(Duration::from_millis(30)).await;
+        assert_eq!(breaker.state(), CircuitState::HalfOpen);
+
+        fail(&breaker).await.unwrap().unwrap_err();
+        assert_eq!(breaker.state(), CircuitState::Open);
+        assert!(succeed(&breaker).await.is_err());
+    }
+
+    #[tokio::test]
+    async fn only_one_trial_runs_while_half_open() {
+        let breaker = breaker(100, 1);
+        fail(&breaker).await.unwrap().unwrap_err();
+        tokio::time::sleep(Duration::from_millis(30)).await;
+
+        let (release, released) = tokio::sync::oneshot::channel::<()>();
+        let trial = breaker.call(async {
+            let _ = released.await;
+            Ok::<_, ()>(())
+        });
+        let other = async {
+            let rejected = succeed(&breaker).await.is_err();
+            let _ = release.send(());
+            rejected
+        };
+        let (trial, rejected) = tokio::join!(trial, other);
+        assert!(trial.is_ok());
+        assert!(rejected);
+        assert_eq!(breaker.state(), CircuitState::Closed);
+    }
+
+    #[tokio::test]
+    async fn the_failure_rate_covers_the_latest_calls_once_the_window_is_full() {
+        let breaker = breaker(50, 4);
+        fail(&breaker).await.unwrap().unwrap_err();
+        for _ in 0..2 {
+            succeed(&breaker).await.unwrap().unwrap();
+        }
+        assert_eq!(breaker.metrics().failure_rate, None);
+        succeed(&breaker).await.unwrap().unwrap();
+        assert_eq!(breaker.metrics().failure_rate, Some(25.0));
+
+        // The first failure leaves the window, so one more is below 50%
+        fail(&breaker).await.unwrap().unwrap_err();
+        assert_eq!(breaker.metrics().failure_rate, Some(25.0));
+        assert_eq!(breaker.state(), CircuitState::Closed);
+
+        // Two failures out of the latest four reach it
+        fail(&breaker).await.unwrap().unwrap_err();
+        assert_eq!(breaker.state(), CircuitState::Open);
+    }
+
+    #[tokio::test]
+    async fn a_dropped_call_counts_as_failed() {
+        let breaker = breaker(100, 1);
+        let call = breaker.call(std::future::pending::<Result<(), ()>>());
+        assert!(tokio::time::timeout(Duration::from_millis(10), call).await.is_err());
+
+        assert_eq!(breaker.metrics().failed_calls, 1);
+        assert_eq!(breaker.state(), CircuitState::Open);
+    }
+
+    #[tokio::test]
+    async fn retries_until_success_or_the_attempts_run_out() {
+        let policy = RetryPolicy { max_attempts: 3, backoff: Backoff::Exponential, delay: Duration::from_millis(1) };
+        let mut attempts = 0;
+        let result = policy.run("flaky", || {
+            attempts += 1;
+            let attempt = attempts;
+            async move { if attempt < 3 { Err("unavailable") } else { Ok(attempt) } }
+        }, |_| true).await;
+        assert_eq!(result, Ok(3));
+
+        let mut attempts = 0;
+        let result: Result<(), _> = policy.run("broken", || {
+            attempts += 1;
+            async { Err("unavailable") }
+        }, |_| true).await;
+        assert_eq!((result, attempts), (Err("unavailable"), 3));
+
+        let mut attempts = 0;
+        let result: Result<(), _> = policy.run("rejected", || {
+            attempts += 1;
+            async { Err("invalid") }
+        }, |err| *err != "invalid").await;
+        assert_eq!((result, attempts), (Err("invalid"), 1));
+    }
+}
@@ -0,0 +1,263 @@
+use crate::{ContainerError, HookFuture, ServiceContainer};
+use chrono::{DateTime, Local, Utc};
+use chrono_tz::Tz;
+use cron::Schedule;
+use std::any::TypeId;
+use std::fmt;
+use std::str::FromStr;
+use std::sync::Arc;
+use std::time::Duration;
+use tokio::sync::watch;
+use tokio::task::JoinHandle;
+use tokio::time::Instant;
+
+// A #[scheduled] method, registered by #[service] and #[controller]
+pub struct ScheduledTask {
+    // The component the method belongs to, which must be registered for it to run
+    pub component: TypeId,
+    // `Type::method`
+    pub name: &'static str,
+    pub trigger: Trigger,
+    // Waited once before the first run
+    pub initial_delay: Duration,
+    pub run: fn(Arc<ServiceContainer>) -> HookFuture,
+}
+
+inventory::collect!(ScheduledTask);
+
+// When a task runs. A run is never started while the previous one is still going.
+#[derive(Debug, Clone, Copy)]
+pub enum Trigger {
+    // Six fields starting with the seconds, e.g. "0 */5 * * * *", in the time zone
+    // named by `zone`, e.g. "Europe/Paris", or else the local one
+    Cron { expression: &'static str, zone: Option<&'static str> },
+    // Every period from the start of the previous run, or right after it when it took longer
+    FixedRate(Duration),
+    // Every period from the end of the previous run
+    FixedDelay(Duration),
+}
+
+impl fmt::Display for Trigger {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Trigger::Cron { expression, zone: Some(zone) } => write!(f, "cron \"{}\" in {}", expression, zone),
+            Trigger::Cron { expression, zone: None } => write!(f, "cron \"{}\"", expression),
+            Trigger::FixedRate(period) => write!(f, "every {:?}", period),
+            Trigger::FixedDelay(delay) => write!(f, "{:?} after each run", delay),
+        }
+    }
+}
+
+// The parsed trigger
+enum Plan {
+    Cron(Box<Schedule>, Option<Tz>),
+    FixedRate(Duration),
+    FixedDelay(Duration),
+}
+
+impl Plan {
+    fn new(task: &ScheduledTask) -> Result<Self, ContainerError> {
+        let invalid = |reason: String| ContainerError::InvalidSchedule { task: task.name, reason };
+        match task.trigger {
+            Trigger::Cron { expression, zone } => {
+                let schedule = Schedule::from_str(expression)
+                    .map_err(|err| invalid(format!("invalid cron expression \"{}\": {}", expression, err)))?;
+                let zone = zone
+                    .map(|zone| zone.parse::<Tz>().map_err(|_| invalid(format!("unknown time zone \"{}\"", zone))))
+                    .transpose()?;
+                Ok(Plan::Cron(Box::new(schedule), zone))
+            }
+            Trigger::FixedRate(period) if period.is_zero() => Err(invalid("fixed_rate must not be zero".to_string())),
+            Trigger::FixedRate(period) => Ok(Plan::FixedRate(period)),
+            Trigger::FixedDelay(delay) if delay.is_zero() => Err(invalid("fixed_delay must not be zero".to_string())),
+            Trigger::FixedDelay(delay) => Ok(Plan::FixedDelay(delay)),
+        }
+    }
+
+    // How long until the next cron time, `None` when there is none
+    fn until_next(schedule: &Schedule, zone: Option<Tz>) -> Option<Duration> {
+        let now = Utc::now();
+        Some((Self::next_time(schedule, zone, now)? - now).to_std().unwrap_or_default())
+    }
+
+    fn next_time(schedule: &Schedule, zone: Option<Tz>, now: DateTime<Utc>) -> Option<DateTime<Utc>> {
+        let next = match zone {
+            Some(zone) => schedule.after(&now.with_timezone(&zone)).next()?.with_timezone(&Utc),
+            None => schedule.after(&now.with_timezone(&Local)).next()?.with_timezone(&Utc),
+        };
+        Some(next)
+    }
+
+    // When to run next, for a run started at `started` and ended at `ended`;
+    // cron runs wait for their next time from there
+    fn after_run(&self, started: Instant, ended: Instant) -> Instant {
+        match self {
+            Plan::Cron(..) => ended,
+            Plan::FixedRate(period) => (started + *period).max(ended),
+            Plan::FixedDelay(delay) => ended + *delay,
+        }
+    }
+}
+
+// Runs the #[scheduled] methods of the components registered in a container until
+// shut down; `Application::run` starts it once the container is built
+pub struct Scheduler {
+    shutdown: watch::Sender<bool>,
+    tasks: Vec<JoinHandle<()>>,
+}
+
+impl Scheduler {
+    // Fails without starting anything if a cron expression or time zone is invalid
+    pub fn start(container: &Arc<ServiceContainer>) -> Result<Self, ContainerError> {
+        let mut tasks: Vec<&'static ScheduledTask> = inventory::iter::<ScheduledTask>()
+            .filter(|task| !container.candidates_for(task.component).is_empty())
+            .collect();
+        tasks.sort_by_key(|task| task.name);
+        let plans = tasks.iter().map(|task| Plan::new(task)).collect::<Result<Vec<_>, _>>()?;
+
+        let (shutdown, stopped) = watch::channel(false);
+        let tasks = tasks.into_iter().zip(plans)
+            .map(|(task, plan)| {
+                println!("[rspring] Scheduled {} ({})", task.name, task.trigger);
+                tokio::spawn(run(task, plan, container.clone(), stopped.clone()))
+            })
+            .collect();
+        Ok(Self { shutdown, tasks })
+    }
+
+    // Stops scheduling runs and waits for those in progress to finish
+    pub async fn shutdown(self) {
+        let _ = self.shutdown.send(true);
+        for task in self.tasks {
+            let _ = task.await;
+        }
+    }
+}
+
+async fn run(task: &'static ScheduledTask, plan: Plan, container: Arc<ServiceContainer>, mut stopped: watch::Receiver<bool>) {
+    let mut next = Instant::now() + task.initial_delay;
+    loop {
+        if let Plan::Cron(schedule, zone) = &plan {
+            let Some(delay) = Plan::until_next(schedule, *zone) else {
+                println!("[rspring] Scheduled task {} has no upcoming time, stopping it", task.name);
+                return;
+            };
+            next = next.max(Instant::now() + delay);
+        }
+        tokio::select! {
+            _ = tokio::time::sleep_until(next) => {}
+            _ = stopped.changed() => return,
+        }
+
+        let started = Instant::now();
+        // On its own task, so that a panic only fails this run
+        match tokio::spawn((task.run)(container.clone())).await {
+            Ok(Ok(())) => {}
+            Ok(Err(err)) => eprintln!("[rspring] Scheduled task {} failed: {}", task.name, err),
+            Err(err) => eprintln!("[rspring] Scheduled task {} panicked: {}", task.name, err),
+        }
+
+        next = plan.after_run(started, Instant::now());
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_support::component;
+    use crate::{Component, Condition, Environment, Instance};
+    use std::sync::atomic::{AtomicUsize, Ordering};
+
+    const fn task(trigger: Trigger) -> ScheduledTask {
+        ScheduledTask {
+            component: TypeId::of::<Ticker>(),
+            name: "Ticker::tick",
+            trigger,
+            initial_delay: Duration::ZERO,
+            run: |_| Box::pin(async { Ok(()) }),
+        }
+    }
+
+    #[test]
+    fn invalid_triggers_are_rejected() {
+        let triggers = [
+            Trigger::Cron { expression: "every day", zone: None },
+            Trigger::Cron { expression: "0 0 9 * * *", zone: Some("Mars/Olympus") },
+            Trigger::FixedRate(Duration::ZERO),
+            Trigger::FixedDelay(Duration::ZERO),
+        ];
+        for trigger in triggers {
+            let plan = Plan::new(&task(trigger));
+            assert!(matches!(plan, Err(ContainerError::InvalidSchedule { task: "Ticker::tick", .. })), "{}", trigger);
+        }
+    }
+
+    #[test]
+    fn cron_times_are_in_the_zone() {
+        let schedule = Schedule::from_str("0 0 9 * * *").unwrap();
+        let next = |zone: &str, now: &str| {
+            let now = now.parse::<DateTime<Utc>>().unwrap();
+            Plan::next_time(&schedule, Some(zone.parse().unwrap()), now).unwrap().to_rfc3339()
+        };
+
+        assert_eq!(next("America/New_York", "2026-01-15T12:00:00Z"), "2026-01-15T14:00:00+00:00");
+        assert_eq!(next("America/New_York", "2026-07-15T12:00:00Z"), "2026-07-15T13:00:00+00:00");
+        assert_eq!(next("Asia/Tokyo", "2026-01-15T12:00:00Z"), "2026-01-16T00:00:00+00:00");
+    }
+
+    #[test]
+    fn fixed_rates_count_from_the_start_and_fixed_delays_from_the_end() {
+        let started = Instant::now();
+        let ended = started + Duration::from_secs(3);
+
+        assert_eq!(Plan::FixedRate(Duration::from_secs(10)).after_run(started, ended), started + Duration::from_secs(10));
+        // A run longer than the period is followed right away
+        assert_eq!(Plan::FixedRate(Duration::from_secs(2)).after_run(started, ended), ended);
+        assert_eq!(Plan::FixedDelay(Duration::from_secs(10)).after_run(started, ended), ended + Duration::from_secs(10));
+    }
+
+    struct Ticker;
+
+    static STARTED: AtomicUsize = AtomicUsize::new(0);
+    static FINISHED: AtomicUsize = AtomicUsize::new(0);
+
+    // Only registered by the test below, so other containers do not schedule it
+    inventory::submit! {
+        Component {
+            conditions: &[Condition::OnProperty { name: "test.scheduler", having_value: None, match_if_missing: false }],
+            build: |_| Box::pin(async { Ok(Arc::new(Arc::new(Ticker)) as Instance) }),
+            ..component::<Ticker>("Ticker", &[])
+        }
+    }
+
+    inventory::submit! {
+        ScheduledTask {
+            run: |_| Box::pin(async {
+                STARTED.fetch_add(1, Ordering::SeqCst);
+                tokio::time::sleep(Duration::from_millis(20)).await;
+                FINISHED.fetch_add(1, Ordering::SeqCst);
+                Ok(())
+            }),
+            ..task(Trigger::FixedRate(Duration::from_millis(5)))
+        }
+    }
+
+    #[tokio::test]
+    async fn shutdown_waits_for_the_current_run_and_stops_scheduling() {
+        let container = ServiceContainer::builder()
+            .environment(Environment::new())
+            .property("test.scheduler", "true")
+            .try_build_async()
+            .await
+            .unwrap();
+        let scheduler = Scheduler::start(&container).unwrap();
+        tokio::time::sleep(Duration::from_millis(30)).await;
+        scheduler.shutdown().await;
+
+        let runs = STARTED.load(Ordering::SeqCst);
+        assert!(runs > 0);
+        assert_eq!(FINISHED.load(Ordering::SeqCst), runs);
+        tokio::time::sleep(Duration::from_millis(30)).await;
+        assert_eq!(STARTED.load(Ordering::SeqCst), runs);
+    }
+}
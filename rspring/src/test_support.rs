@@ -0,0 +1,24 @@
+use crate::{Component, Dependency, Scope};
+use std::any::TypeId;
+
+// A singleton named `name` for tests of what the container does with the
+// registered metadata; building it fails
+pub(crate) const fn component<T: 'static>(name: &'static str, dependencies: &'static [Dependency]) -> Component {
+    Component {
+        name,
+        type_id: TypeId::of::<T>(),
+        type_name: std::any::type_name::<T>,
+        module_path: module_path!(),
+        tags: &[],
+        scope: Scope::Singleton,
+        primary: false,
+        order: 0,
+        lazy: false,
+        conditions: &[],
+        provides: &[],
+        dependencies,
+        build: |_| Box::pin(async { Err("never built".into()) }),
+        post_construct: None,
+        pre_destroy: None,
+    }
+}
@@ -0,0 +1,80 @@
+use rspring::*;
+use rspring_macro::*;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Arc;
+
+struct Counting {
+    calls: AtomicUsize,
+}
+#[service]
+impl Counting {
+    pub fn new() -> Self {
+        Self { calls: AtomicUsize::new(0) }
+    }
+}
+
+impl Interceptor for Counting {
+    async fn intercept(&self, _invocation: &Invocation, proceed: Proceed<'_>) -> ReturnValue {
+        self.calls.fetch_add(1, Ordering::Relaxed);
+        proceed.proceed().await
+    }
+}
+
+struct Inventory {
+    attempts: AtomicUsize,
+    computed: AtomicUsize,
+    collaborators: Collaborators,
+}
+#[service]
+#[intercept(Counting)]
+impl Inventory {
+    pub fn new(collaborators: Collaborators) -> Self {
+        Self { attempts: AtomicUsize::new(0), computed: AtomicUsize::new(0), collaborators }
+    }
+
+    #[retryable(max_attempts = 3, delay = "1ms")]
+    pub async fn reserve(&self) -> Result<usize, String> {
+        match self.attempts.fetch_add(1, Ordering::Relaxed) + 1 {
+            attempt if attempt < 3 => Err(format!("attempt {} failed", attempt)),
+            attempt => Ok(attempt),
+        }
+    }
+
+    pub async fn shared(self: Arc<Self>) -> usize {
+        Arc::strong_count(&self)
+    }
+
+    #[cacheable("stock")]
+    pub async fn stock(&self, item_id: u32) -> u32 {
+        self.computed.fetch_add(1, Ordering::Relaxed);
+        item_id * 10
+    }
+}
+
+async fn container() -> (Arc<Inventory>, Arc<Counting>) {
+    let container = ServiceContainer::builder().try_build_async().await.unwrap();
+    (container.get::<Inventory>(), container.get::<Counting>())
+}
+
+#[tokio::test]
+async fn retried_calls_are_intercepted_once() {
+    let (inventory, counting) = container().await;
+    assert_eq!(inventory.reserve().await, Ok(3));
+    assert_eq!(counting.calls.load(Ordering::Relaxed), 1);
+}
+
+#[tokio::test]
+async fn cached_calls_are_intercepted_once_each() {
+    let (inventory, counting) = container().await;
+    assert_eq!(inventory.stock(2).await, 20);
+    assert_eq!(inventory.stock(2).await, 20);
+    assert_eq!(inventory.computed.load(Ordering::Relaxed), 1);
+    assert_eq!(counting.calls.load(Ordering::Relaxed), 2);
+}
+
+#[tokio::test]
+async fn methods_taking_an_arc_are_intercepted() {
+    let (inventory, counting) = container().await;
+    assert!(inventory.clone().shared().await > 1);
+    assert_eq!(counting.calls.load(Ordering::Relaxed), 1);
+}
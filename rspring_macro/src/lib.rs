@@ -1,59 +1,486 @@
 use proc_macro::TokenStream;
+use proc_macro2::{Group, TokenStream as TokenStream2, TokenTree};
 use quote::quote;
-use syn::{parse_macro_input, ItemFn, LitStr, ItemStruct, Type, ItemImpl, ImplItem, FnArg};
+use syn::parse::Parse;
+use syn::punctuated::Punctuated;
+use syn::{parse_macro_input, parse_quote, Attribute, ItemFn, LitStr, ItemStruct, Type, ItemImpl, ImplItem, ImplItemFn, FnArg};
+
+// Attributes of the methods wrapped by #[service]/#[controller]
+const WRAPPERS: &[&str] = &[
+    "intercept",
+    "cacheable",
+    "cache_put",
+    "cache_evict",
+    "retryable",
+    "circuit_breaker",
+    "async_method",
+];
+
+// Attributes read by #[service]/#[controller] when placed below them
+const MARKERS: &[&str] = &[
+    "primary",
+    "lazy",
+    "order",
+    "profile",
+    "conditional_on_property",
+    "conditional_on_bean",
+    "conditional_on_missing_bean",
+    "intercept",
+];
+
+// ("name", having_value = "v", match_if_missing = true)
+fn parse_property_condition(input: syn::parse::ParseStream) -> syn::Result<TokenStream2> {
+    let name: LitStr = input.parse()?;
+    let mut having_value = quote! { None };
+    let mut match_if_missing = false;
+
+    while !input.is_empty() {
+        input.parse::<syn::Token![,]>()?;
+        if input.is_empty() {
+            break;
+        }
+        let key: syn::Ident = input.parse()?;
+        input.parse::<syn::Token![=]>()?;
+        if key == "having_value" {
+            let value: LitStr = input.parse()?;
+            having_value = quote! { Some(#value) };
+        } else if key == "match_if_missing" {
+            match_if_missing = input.parse::<syn::LitBool>()?.value;
+        } else {
+            return Err(syn::Error::new(key.span(), "expected `having_value` or `match_if_missing`"));
+        }
+    }
+
+    Ok(quote! {
+        rspring::Condition::OnProperty {
+            name: #name,
+            having_value: #having_value,
+            match_if_missing: #match_if_missing,
+        }
+    })
+}
+
+// Arguments accepted by #[service(...)] and #[controller(...)],
+// plus marker attributes such as #[primary] written next to them
+#[derive(Clone)]
+struct ComponentArgs {
+    name: Option<LitStr>,
+    scope: TokenStream2,
+    primary: bool,
+    lazy: bool,
+    order: Option<syn::LitInt>,
+    conditions: Vec<TokenStream2>,
+    provides: Vec<Type>,
+    // Concrete types a generic component is registered for, one component each
+    instances: Vec<Type>,
+    // Labels to select the component by when building an ApplicationContext
+    tags: Vec<LitStr>,
+    // #[intercept(..)] on the impl block
+    interceptors: Vec<Type>,
+    // Components the wrapped methods call, e.g. their interceptors, resolved into
+    // the `collaborators` field when the component is built
+    wrapped_dependencies: Vec<Type>,
+    // The first wrapped method, where a missing `Collaborators` is reported
+    wrapped_at: Option<proc_macro2::Span>,
+    // #[controller("/api/orders")], prefixed to the path of every route
+    base_path: Option<LitStr>,
+    post_construct: Option<TokenStream2>,
+    pre_destroy: Option<TokenStream2>,
+}
+
+impl Default for ComponentArgs {
+    fn default() -> Self {
+        Self {
+            name: None,
+            scope: quote! { rspring::Scope::Singleton },
+            primary: false,
+            lazy: false,
+            order: None,
+            conditions: Vec::new(),
+            provides: Vec::new(),
+            instances: Vec::new(),
+            tags: Vec::new(),
+            interceptors: Vec::new(),
+            wrapped_dependencies: Vec::new(),
+            wrapped_at: None,
+            base_path: None,
+            post_construct: None,
+            pre_destroy: None,
+        }
+    }
+}
+
+impl ComponentArgs {
+    fn parse(&mut self, meta: syn::meta::ParseNestedMeta) -> syn::Result<()> {
+        if meta.path.is_ident("name") {
+            self.name = Some(meta.value()?.parse()?);
+            Ok(())
+        } else if meta.path.is_ident("scope") {
+            let scope: LitStr = meta.value()?.parse()?;
+            self.scope = match scope.value().as_str() {
+                "singleton" => quote! { rspring::Scope::Singleton },
+                "request" => quote! { rspring::Scope::Request },
+                "prototype" => quote! { rspring::Scope::Prototype },
+                _ => return Err(syn::Error::new(
+                    scope.span(),
+                    "expected scope \"singleton\", \"request\" or \"prototype\"",
+                )),
+            };
+            Ok(())
+        } else if meta.path.is_ident("provides") {
+            // provides = dyn Trait, or provides(dyn A, dyn B)
+            if meta.input.peek(syn::Token![=]) {
+                self.provides.push(meta.value()?.parse()?);
+            } else {
+                let content;
+                syn::parenthesized!(content in meta.input);
+                self.provides.extend(content.parse_terminated(Type::parse, syn::Token![,])?);
+            }
+            Ok(())
+        } else if meta.path.is_ident("instances") {
+            // instances(User, Order), or instances((User, Db), ..) for several type parameters
+            let content;
+            syn::parenthesized!(content in meta.input);
+            self.instances.extend(content.parse_terminated(Type::parse, syn::Token![,])?);
+            Ok(())
+        } else if meta.path.is_ident("tags") {
+            let content;
+            syn::parenthesized!(content in meta.input);
+            self.tags.extend(content.parse_terminated(<LitStr as Parse>::parse, syn::Token![,])?);
+            Ok(())
+        } else {
+            Err(meta.error("unsupported component argument"))
+        }
+    }
+
+    // Consume the marker attributes placed below #[service]/#[controller]
+    fn take_markers(&mut self, attrs: &mut Vec<Attribute>) -> syn::Result<()> {
+        let mut markers = Vec::new();
+        attrs.retain(|attr| {
+            let is_marker = MARKERS.iter().any(|marker| attr.path().is_ident(marker));
+            if is_marker {
+                markers.push(attr.clone());
+            }
+            !is_marker
+        });
+
+        for attr in markers {
+            let path = attr.path();
+            if path.is_ident("primary") {
+                self.primary = true;
+            } else if path.is_ident("lazy") {
+                self.lazy = true;
+            } else if path.is_ident("order") {
+                self.order = Some(attr.parse_args()?);
+            } else if path.is_ident("profile") {
+                let profiles = attr.parse_args_with(Punctuated::<LitStr, syn::Token![,]>::parse_terminated)?;
+                let profiles = profiles.iter();
+                self.conditions.push(quote! { rspring::Condition::Profile(&[#(#profiles),*]) });
+            } else if path.is_ident("conditional_on_bean") {
+                let ty: Type = attr.parse_args()?;
+                self.conditions.push(quote! { rspring::Condition::OnBean(rspring::Dependency::of::<#ty>()) });
+            } else if path.is_ident("conditional_on_missing_bean") {
+                let ty: Type = attr.parse_args()?;
+                self.conditions.push(quote! { rspring::Condition::OnMissingBean(rspring::Dependency::of::<#ty>()) });
+            } else if path.is_ident("intercept") {
+                self.interceptors.extend(attr.parse_args_with(Punctuated::<Type, syn::Token![,]>::parse_terminated)?);
+            } else {
+                self.conditions.push(attr.parse_args_with(parse_property_condition)?);
+            }
+        }
+        Ok(())
+    }
+
+    // Find the #[post_construct] and #[pre_destroy] methods of an impl block,
+    // removing the attributes, and generate the hooks calling them
+    fn take_lifecycle_hooks(&mut self, self_ty: &Type, items: &mut [ImplItem]) -> syn::Result<()> {
+        for item in items {
+            let ImplItem::Fn(method) = item else {
+                continue;
+            };
+            for hook in ["post_construct", "pre_destroy"] {
+                let Some(pos) = method.attrs.iter().position(|attr| attr.path().is_ident(hook)) else {
+                    continue;
+                };
+                let attr = method.attrs.remove(pos);
+                let slot = if hook == "post_construct" { &mut self.post_construct } else { &mut self.pre_destroy };
+                if slot.is_some() {
+                    return Err(syn::Error::new_spanned(attr, format!("only one #[{}] method is allowed", hook)));
+                }
+
+                let method_name = &method.sig.ident;
+                let awaited = method.sig.asyncness.map(|_| quote! { .await });
+                let fallible = returns_result(&method.sig).then(|| quote! { ? });
+                *slot = Some(quote! {
+                    |instance| std::boxed::Box::pin(async move {
+                        let component = instance.downcast_ref::<std::sync::Arc<#self_ty>>().unwrap().clone();
+                        component.#method_name()#awaited #fallible;
+                        Ok::<(), rspring::BuildError>(())
+                    })
+                });
+            }
+        }
+        Ok(())
+    }
+
+    fn hooks(&self) -> (TokenStream2, TokenStream2) {
+        let hook = |hook: &Option<TokenStream2>| match hook {
+            Some(hook) => quote! { Some(#hook) },
+            None => quote! { None },
+        };
+        (hook(&self.post_construct), hook(&self.pre_destroy))
+    }
+
+    fn order(&self) -> TokenStream2 {
+        match &self.order {
+            Some(order) => quote! { #order },
+            None => quote! { i32::MAX },
+        }
+    }
+
+    // The explicit `name = ".."`, or the type as written, e.g. `CrudService<User>`
+    fn component_name(&self, self_ty: &TokenStream2) -> String {
+        match &self.name {
+            Some(name) => name.value(),
+            None => type_string(self_ty),
+        }
+    }
+
+    // The `provides` table for `self_ty`, casting the built instance to each listed type
+    fn provided_types(&self, self_ty: &TokenStream2) -> TokenStream2 {
+        let provided = self.provides.iter().map(|ty| quote! {
+            rspring::ProvidedType {
+                type_id: std::any::TypeId::of::<#ty>(),
+                type_name: std::any::type_name::<#ty>,
+                cast: |instance| {
+                    let concrete = instance.downcast_ref::<std::sync::Arc<#self_ty>>().unwrap().clone();
+                    std::sync::Arc::new(concrete as std::sync::Arc<#ty>)
+                }
+            }
+        });
+        quote! { &[#(#provided),*] }
+    }
+}
 
 // #[service] and #[controller] do the SAME thing
 #[proc_macro_attribute]
-pub fn service(_attr: TokenStream, item: TokenStream) -> TokenStream {
+pub fn service(attr: TokenStream, item: TokenStream) -> TokenStream {
+    let mut args = ComponentArgs::default();
+    let parser = syn::meta::parser(|meta| args.parse(meta));
+    parse_macro_input!(attr with parser);
+
     // Try parsing as struct first
-    if let Ok(_) = syn::parse::<ItemStruct>(item.clone()) {
-        return component_macro(item);
+    if syn::parse::<ItemStruct>(item.clone()).is_ok() {
+        return component_macro(item, args);
     }
     
     // Try parsing as impl
     if let Ok(input) = syn::parse::<ItemImpl>(item) {
-        return service_impl_macro(input);
+        return service_impl_macro(input, args);
     }
 
     panic!("#[service] can only be used on structs or impl blocks");
 }
 
+// #[controller("/api/orders")] on the impl block prefixes the path of its routes
+#[proc_macro_attribute]
+pub fn controller(attr: TokenStream, item: TokenStream) -> TokenStream {
+    controller_macro("controller", attr, item)
+}
+
+// Same as #[controller], handlers already return their response body
 #[proc_macro_attribute]
-pub fn controller(_attr: TokenStream, item: TokenStream) -> TokenStream {
+pub fn rest_controller(attr: TokenStream, item: TokenStream) -> TokenStream {
+    controller_macro("rest_controller", attr, item)
+}
+
+fn controller_macro(macro_name: &str, attr: TokenStream, item: TokenStream) -> TokenStream {
+    let mut args = ComponentArgs::default();
+    // An optional base path, then the same arguments as #[service]
+    let parser = |input: syn::parse::ParseStream| {
+        if input.peek(LitStr) {
+            args.base_path = Some(input.parse()?);
+            if !input.is_empty() {
+                input.parse::<syn::Token![,]>()?;
+            }
+        }
+        let rest: TokenStream2 = input.parse()?;
+        syn::parse::Parser::parse2(syn::meta::parser(|meta| args.parse(meta)), rest)
+    };
+    parse_macro_input!(attr with parser);
+
     // Try parsing as struct first
-    if let Ok(_) = syn::parse::<ItemStruct>(item.clone()) {
-        return component_macro(item);
+    if syn::parse::<ItemStruct>(item.clone()).is_ok() {
+        return component_macro(item, args);
     }
     
     // Try parsing as impl
     if let Ok(input) = syn::parse::<ItemImpl>(item) {
-        return controller_impl_macro(input);
+        return controller_impl_macro(input, args);
     }
 
-    panic!("#[controller] can only be used on structs or impl blocks");
+    panic!("#[{}] can only be used on structs or impl blocks", macro_name);
+}
+
+// Marks the default choice among several components of the same type
+#[proc_macro_attribute]
+pub fn primary(attr: TokenStream, item: TokenStream) -> TokenStream {
+    forward_marker("primary", attr, item)
+}
+
+// Build the component on first use instead of at startup
+#[proc_macro_attribute]
+pub fn lazy(attr: TokenStream, item: TokenStream) -> TokenStream {
+    forward_marker("lazy", attr, item)
+}
+
+// Position of the component when injected into a `Vec<Arc<T>>`, lowest first
+#[proc_macro_attribute]
+pub fn order(attr: TokenStream, item: TokenStream) -> TokenStream {
+    forward_marker("order", attr, item)
+}
+
+// Register the component only when a property is set, or has a given value
+#[proc_macro_attribute]
+pub fn conditional_on_property(attr: TokenStream, item: TokenStream) -> TokenStream {
+    forward_marker("conditional_on_property", attr, item)
+}
+
+// Register the component only when one of the profiles is active
+#[proc_macro_attribute]
+pub fn profile(attr: TokenStream, item: TokenStream) -> TokenStream {
+    forward_marker("profile", attr, item)
+}
+
+// Register the component only when a component of the given type is registered
+#[proc_macro_attribute]
+pub fn conditional_on_bean(attr: TokenStream, item: TokenStream) -> TokenStream {
+    forward_marker("conditional_on_bean", attr, item)
+}
+
+// Register the component only when no other component of the given type is registered
+#[proc_macro_attribute]
+pub fn conditional_on_missing_bean(attr: TokenStream, item: TokenStream) -> TokenStream {
+    forward_marker("conditional_on_missing_bean", attr, item)
+}
+
+// #[intercept(A, B)] on a method, or on an impl block for all its async methods,
+// runs every call through the Interceptor components A then B, taken from the
+// `collaborators: Collaborators` field of the component. Read by
+// #[service]/#[controller], which also make the component depend on them; on a
+// plain impl block the interceptors are only resolved when the component is built.
+#[proc_macro_attribute]
+pub fn intercept(attr: TokenStream, item: TokenStream) -> TokenStream {
+    let is_component = |attr: &Attribute| {
+        attr.path().segments.last().is_some_and(|s| matches!(s.ident.to_string().as_str(), "service" | "controller" | "rest_controller"))
+    };
+    let mut input = match syn::parse::<ItemImpl>(item.clone()) {
+        Ok(input) if !input.attrs.iter().any(is_component) => input,
+        _ => return forward_marker("intercept", attr, item),
+    };
+    let interceptors = parse_macro_input!(attr with Punctuated::<Type, syn::Token![,]>::parse_terminated);
+    let interceptors: Vec<Type> = interceptors.into_iter().collect();
+    let written = method_names(&input.items);
+    let used = match intercept_methods(&input.self_ty, &mut input.items, &interceptors, &written) {
+        Ok(used) => used,
+        Err(err) => return err.to_compile_error().into(),
+    };
+    // A generic impl has no single type to register the interceptors for
+    let wrapped = input.generics.params.is_empty().then(|| wrapped_methods(&input.self_ty, &used));
+    quote! {
+        #input
+        #wrapped
+    }.into()
+}
+
+// Markers are read by #[service]/#[controller]. When a marker is written above
+// them it expands first, so move it below where the component macro will see it
+fn forward_marker(marker: &str, attr: TokenStream, item: TokenStream) -> TokenStream {
+    let marker = quote::format_ident!("{}", marker);
+    let attr = TokenStream2::from(attr);
+    let mut item = parse_macro_input!(item as syn::Item);
+
+    let attrs = match &mut item {
+        syn::Item::Impl(item) => &mut item.attrs,
+        syn::Item::Struct(item) => &mut item.attrs,
+        _ => return syn::Error::new_spanned(&item, format!("#[{}] can only be used on components", marker))
+            .to_compile_error()
+            .into(),
+    };
+    let is_component = |attr: &Attribute| {
+        attr.path().segments.last().is_some_and(|s| matches!(s.ident.to_string().as_str(), "service" | "controller" | "rest_controller"))
+    };
+    let Some(pos) = attrs.iter().position(is_component) else {
+        return syn::Error::new_spanned(&item, format!("#[{}] must be used together with #[service] or #[controller]", marker))
+            .to_compile_error()
+            .into();
+    };
+
+    let marker_attr: Attribute = if attr.is_empty() {
+        parse_quote! { #[#marker] }
+    } else {
+        parse_quote! { #[#marker(#attr)] }
+    };
+    attrs.insert(pos + 1, marker_attr);
+
+    quote! { #item }.into()
+}
+
+fn component_macro(item: TokenStream, mut args: ComponentArgs) -> TokenStream {
+    let mut input = parse_macro_input!(item as ItemStruct);
+    let registrations = args.take_markers(&mut input.attrs)
+        .and_then(|_| match args.interceptors.first() {
+            Some(interceptor) => Err(syn::Error::new_spanned(interceptor, "#[intercept] goes on the impl block with the methods to wrap")),
+            None => Ok(()),
+        })
+        .and_then(|_| match &args.base_path {
+            Some(base_path) => Err(syn::Error::new_spanned(base_path, "the base path goes on the impl block with the routes")),
+            None => Ok(()),
+        })
+        .and_then(|_| instantiations(&input.generics, &args))
+        .and_then(|instantiations| {
+            let name = input.ident.clone();
+            if instantiations.is_empty() {
+                return struct_registration(&mut input, quote!(#name), &args).map(|r| vec![r]);
+            }
+            let registrations = instantiations.iter()
+                .map(|instantiation| {
+                    let mut concrete: ItemStruct = instantiation.apply(&input)?;
+                    let type_args = &instantiation.args;
+                    struct_registration(&mut concrete, quote!(#name<#(#type_args),*>), &args)
+                })
+                .collect::<syn::Result<Vec<_>>>()?;
+            // Only strips the injection attributes, the generic struct is not registered itself
+            struct_registration(&mut input, quote!(#name), &args)?;
+            Ok(registrations)
+        });
+
+    match registrations {
+        Ok(registrations) => quote! {
+            #input
+            #(#registrations)*
+        }.into(),
+        Err(err) => err.to_compile_error().into(),
+    }
 }
 
-fn component_macro(item: TokenStream) -> TokenStream {
-    let input = parse_macro_input!(item as ItemStruct);
+// Registers `self_ty` as a Component built by filling the fields of `input`
+fn struct_registration(input: &mut ItemStruct, self_ty: TokenStream2, args: &ComponentArgs) -> syn::Result<TokenStream2> {
     let name = &input.ident;
-    let name_str = name.to_string();
+    let name_str = args.component_name(&self_ty);
     
     // Extract dependencies from fields
     let mut deps = Vec::new();
-    if let syn::Fields::Named(fields) = &input.fields {
-        for field in &fields.named {
-            if let Type::Path(type_path) = &field.ty {
-                // Check if it's Arc<SomeType>
-                if let Some(segment) = type_path.path.segments.last() {
-                    if segment.ident == "Arc" {
-                        if let syn::PathArguments::AngleBracketed(args) = &segment.arguments {
-                            if let Some(syn::GenericArgument::Type(inner_ty)) = args.args.first() {
-                                deps.push(quote! { std::any::TypeId::of::<#inner_ty>() });
-                            }
-                        }
-                    }
-                }
+    let mut field_inits = Vec::new();
+    for field in input.fields.iter_mut() {
+        let field_name = &field.ident;
+        match injection(&field.ty, &mut field.attrs, &self_ty)? {
+            Some(injection) => {
+                let value = injection.value;
+                deps.extend(injection.dependency);
+                field_inits.push(quote! { #field_name: #value });
             }
+            None => field_inits.push(quote! { #field_name: Default::default() }),
         }
     }
     
@@ -64,174 +491,1296 @@ fn component_macro(item: TokenStream) -> TokenStream {
         quote! { &[#(#deps),*] }
     };
     
-    // Generate field initializations
-    let field_inits = if let syn::Fields::Named(fields) = &input.fields {
-        fields.named.iter().map(|field| {
-            let field_name = &field.ident;
-            if let Type::Path(type_path) = &field.ty {
-                if let Some(segment) = type_path.path.segments.last() {
-                    if segment.ident == "Arc" {
-                        if let syn::PathArguments::AngleBracketed(args) = &segment.arguments {
-                            if let Some(syn::GenericArgument::Type(inner_ty)) = args.args.first() {
-                                return quote! { #field_name: container.get::<#inner_ty>() };
-                            }
-                        }
-                    }
-                }
-            }
-            quote! { #field_name: Default::default() }
-        }).collect::<Vec<_>>()
-    } else {
-        vec![]
-    };
+    let scope = &args.scope;
+    let primary = args.primary;
+    let order = args.order();
+    let lazy = args.lazy;
+    let conditions = &args.conditions;
+    let tags = &args.tags;
+    let provides = args.provided_types(&self_ty);
+    let (post_construct, pre_destroy) = args.hooks();
     
-    quote! {
-        #input
-        
+    Ok(quote! {
         rspring::inventory::submit! {
             rspring::Component {
                 name: #name_str,
-                type_id: std::any::TypeId::of::<#name>(),
+                type_id: std::any::TypeId::of::<#self_ty>(),
+                type_name: std::any::type_name::<#self_ty>,
+                module_path: module_path!(),
+                tags: &[#(#tags),*],
+                scope: #scope,
+                primary: #primary,
+                order: #order,
+                lazy: #lazy,
+                conditions: &[#(#conditions),*],
+                provides: #provides,
                 dependencies: #deps_array,
-                build: |container| {
-                    std::sync::Arc::new(#name {
+                build: |container| std::boxed::Box::pin(async move {
+                    let instance: #self_ty = #name {
                         #(#field_inits),*
-                    })
+                    };
+                    Ok::<_, rspring::BuildError>(std::sync::Arc::new(std::sync::Arc::new(instance)) as rspring::Instance)
+                }),
+                post_construct: #post_construct,
+                pre_destroy: #pre_destroy,
+            }
+        }
+    })
+}
+
+// One concrete instantiation of a generic component, see #[service(instances(..))]
+struct Instantiation {
+    params: Vec<syn::Ident>,
+    args: Vec<Type>,
+}
+
+impl Instantiation {
+    // A copy of `item` without its generics, with the type parameters replaced
+    fn apply<T: HasGenerics + Parse + quote::ToTokens + Clone>(&self, item: &T) -> syn::Result<T> {
+        let mut item = item.clone();
+        *item.generics_mut() = syn::Generics::default();
+        syn::parse2(self.substitute(quote!(#item)))
+    }
+
+    fn substitute(&self, tokens: TokenStream2) -> TokenStream2 {
+        tokens.into_iter()
+            .map(|token| match token {
+                TokenTree::Ident(ident) => match self.params.iter().position(|param| *param == ident) {
+                    // Grouped so `T::new()` becomes `<Vec<u8>>::new()` rather than `Vec<u8>::new()`
+                    Some(i) => {
+                        let ty = &self.args[i];
+                        TokenTree::Group(Group::new(proc_macro2::Delimiter::None, quote!(#ty)))
+                    }
+                    None => TokenTree::Ident(ident),
+                },
+                TokenTree::Group(group) => {
+                    let mut substituted = Group::new(group.delimiter(), self.substitute(group.stream()));
+                    substituted.set_span(group.span());
+                    TokenTree::Group(substituted)
                 }
+                token => token,
+            })
+            .collect()
+    }
+}
+
+trait HasGenerics {
+    fn generics_mut(&mut self) -> &mut syn::Generics;
+}
+
+impl HasGenerics for ItemStruct {
+    fn generics_mut(&mut self) -> &mut syn::Generics {
+        &mut self.generics
+    }
+}
+
+impl HasGenerics for ItemImpl {
+    fn generics_mut(&mut self) -> &mut syn::Generics {
+        &mut self.generics
+    }
+}
+
+// The instantiations listed in `instances(..)`; empty for a component that is not generic
+fn instantiations(generics: &syn::Generics, args: &ComponentArgs) -> syn::Result<Vec<Instantiation>> {
+    let mut params = Vec::new();
+    for param in &generics.params {
+        match param {
+            syn::GenericParam::Type(param) => params.push(param.ident.clone()),
+            _ => return Err(syn::Error::new_spanned(param, "components can only be generic over types")),
+        }
+    }
+
+    match (params.is_empty(), args.instances.is_empty()) {
+        (true, true) => return Ok(Vec::new()),
+        (true, false) => return Err(syn::Error::new_spanned(&args.instances[0], "`instances` is only for generic components")),
+        (false, true) => return Err(syn::Error::new_spanned(
+            generics,
+            "generic components need the types to register them for, e.g. #[service(instances(User, Order))]",
+        )),
+        (false, false) => {}
+    }
+    if let Some(name) = &args.name {
+        return Err(syn::Error::new(name.span(), "`name` cannot be combined with `instances`"));
+    }
+
+    args.instances.iter()
+        .map(|instance| {
+            let args = match instance {
+                Type::Tuple(tuple) if params.len() > 1 => tuple.elems.iter().cloned().collect(),
+                ty => vec![ty.clone()],
+            };
+            if args.len() != params.len() {
+                return Err(syn::Error::new_spanned(
+                    instance,
+                    format!("expected {} types, one for each type parameter", params.len()),
+                ));
+            }
+            Ok(Instantiation { params: params.clone(), args })
+        })
+        .collect()
+}
+
+// Tokens of a type without the spaces `to_string` puts around punctuation
+fn type_string(ty: &TokenStream2) -> String {
+    let text = ty.to_string();
+    let word = |c: Option<char>| c.is_some_and(|c| c.is_alphanumeric() || c == '_');
+    let mut out = String::new();
+    let mut chars = text.chars().peekable();
+    while let Some(c) = chars.next() {
+        if c != ' ' {
+            out.push(c);
+            if c == ',' {
+                out.push(' ');
             }
+        } else if word(out.chars().last()) && word(chars.peek().copied()) {
+            out.push(' ');
         }
     }
-    .into()
+    out
+}
+
+fn service_impl_macro(mut input: ItemImpl, args: ComponentArgs) -> TokenStream {
+    let generated = instantiations(&input.generics, &args).and_then(|instantiations| {
+        if instantiations.is_empty() {
+            return service_registrations(&mut input, args);
+        }
+        let mut generated = Vec::new();
+        for instantiation in &instantiations {
+            generated.extend(service_registrations(&mut instantiation.apply(&input)?, args.clone())?);
+        }
+        // Only strips the attributes, the generic impl is not registered itself
+        service_registrations(&mut input, args)?;
+        Ok(generated)
+    });
+
+    match generated {
+        Ok(generated_items) => quote! {
+            #input
+            #(#generated_items)*
+        }.into(),
+        Err(err) => err.to_compile_error().into(),
+    }
 }
 
-fn service_impl_macro(input: ItemImpl) -> TokenStream {
+fn service_registrations(input: &mut ItemImpl, mut args: ComponentArgs) -> syn::Result<Vec<TokenStream2>> {
+    args.take_markers(&mut input.attrs)?;
+    args.take_lifecycle_hooks(&input.self_ty, &mut input.items)?;
+    wrap_methods(input, &mut args)?;
     let self_ty = &input.self_ty;
-    let mut generated_items = Vec::new();
+    let mut generated_items = vec![wrapped_methods(self_ty, &args.wrapped_dependencies)];
     
     // 1. Check for a `new` method to generate Component registration (Constructor Injection)
-    let mut component_registration = None;
-    
-    for item in &input.items {
+    for item in &mut input.items {
         if let ImplItem::Fn(method) = item {
             if method.sig.ident == "new" {
-                let name_str = quote!(#self_ty).to_string();
-                
-                // Extract dependencies from `new` arguments
-                let mut deps = Vec::new();
-                let mut inject_calls = Vec::new();
-                
-                for arg in &method.sig.inputs {
-                    if let FnArg::Typed(pat_type) = arg {
-                        if let Type::Path(type_path) = &*pat_type.ty {
-                            // Check if it's Arc<SomeType>
-                            if let Some(segment) = type_path.path.segments.last() {
-                                if segment.ident == "Arc" {
-                                    if let syn::PathArguments::AngleBracketed(args) = &segment.arguments {
-                                        if let Some(syn::GenericArgument::Type(inner_ty)) = args.args.first() {
-                                            deps.push(quote! { std::any::TypeId::of::<#inner_ty>() });
-                                            inject_calls.push(quote! { container.get::<#inner_ty>() });
-                                        }
-                                    }
-                                }
-                            }
+                generated_items.push(constructor_registration(self_ty, method, &args)?);
+            }
+        }
+    }
+
+    // 2. Register the #[event_listener] and #[scheduled] methods
+    generated_items.extend(event_listeners(self_ty, &mut input.items)?);
+    generated_items.extend(scheduled_tasks(self_ty, &mut input.items)?);
+    
+    Ok(generated_items)
+}
+
+// Runs the passes wrapping the methods of a component, each wrapping the methods
+// left by the one before, and records the components the wrappers call
+fn wrap_methods(input: &mut ItemImpl, args: &mut ComponentArgs) -> syn::Result<()> {
+    let written = method_names(&input.items);
+    args.wrapped_at = input.items.iter()
+        .find_map(|item| match item {
+            ImplItem::Fn(method) if method.attrs.iter().any(|attr| WRAPPERS.iter().any(|name| attr.path().is_ident(name))) => {
+                Some(method.sig.ident.span())
+            }
+            _ => None,
+        })
+        .or_else(|| args.interceptors.first().map(syn::spanned::Spanned::span));
+    if resilience_methods(&input.self_ty, &mut input.items)? {
+        args.wrapped_dependencies.push(parse_quote! { rspring::CircuitBreakerRegistry });
+    }
+    if cache_methods(&mut input.items)? {
+        args.wrapped_dependencies.push(parse_quote! { rspring::CacheManager });
+    }
+    let interceptors = intercept_methods(&input.self_ty, &mut input.items, &args.interceptors, &written)?;
+    args.wrapped_dependencies.extend(interceptors);
+    if async_methods(&mut input.items)? {
+        args.wrapped_dependencies.push(parse_quote! { rspring::TaskExecutors });
+    }
+    Ok(())
+}
+
+fn method_names(items: &[ImplItem]) -> Vec<syn::Ident> {
+    items.iter()
+        .filter_map(|item| match item {
+            ImplItem::Fn(method) => Some(method.sig.ident.clone()),
+            _ => None,
+        })
+        .collect()
+}
+
+// Registers the components called by the wrapped methods of `self_ty`, which
+// `Collaborators::resolve` looks up when an instance is built
+fn wrapped_methods(self_ty: &Type, dependencies: &[Type]) -> TokenStream2 {
+    if dependencies.is_empty() {
+        return TokenStream2::new();
+    }
+    quote! {
+        rspring::inventory::submit! {
+            rspring::WrappedMethods {
+                component: std::any::TypeId::of::<#self_ty>(),
+                dependencies: &[#(rspring::Dependency::of::<#dependencies>()),*],
+            }
+        }
+    }
+}
+
+// Wrap the async methods marked #[intercept(..)], or all of them when the impl
+// block names interceptors, so that the call goes through each interceptor before
+// reaching the original body, moved to `__intercepted_<method>`. Only the methods
+// `written` by the user are wrapped, not those moved aside by the passes before.
+// Returns every interceptor used, which the component depends on.
+fn intercept_methods(
+    self_ty: &Type,
+    items: &mut Vec<ImplItem>,
+    impl_interceptors: &[Type],
+    written: &[syn::Ident],
+) -> syn::Result<Vec<Type>> {
+    let mut used: Vec<Type> = Vec::new();
+    let mut originals = Vec::new();
+    for item in items.iter_mut() {
+        let ImplItem::Fn(method) = item else {
+            continue;
+        };
+        if !written.contains(&method.sig.ident) {
+            continue;
+        }
+        let mut interceptors = Vec::new();
+        let mut explicit = None;
+        while let Some(pos) = method.attrs.iter().position(|attr| attr.path().is_ident("intercept")) {
+            let attr = method.attrs.remove(pos);
+            interceptors.extend(attr.parse_args_with(Punctuated::<Type, syn::Token![,]>::parse_terminated)?);
+            explicit = Some(attr);
+        }
+        let interceptable = method.sig.asyncness.is_some() && method.sig.receiver().is_some();
+        if let (Some(attr), false) = (&explicit, interceptable) {
+            return Err(syn::Error::new_spanned(attr, "only async methods taking self can be intercepted"));
+        }
+        if !interceptable {
+            continue;
+        }
+        // Interceptors of the impl block wrap those of the method
+        interceptors.splice(0..0, impl_interceptors.iter().cloned());
+        if interceptors.is_empty() {
+            continue;
+        }
+
+        let output = match &method.sig.output {
+            syn::ReturnType::Default => parse_quote! { () },
+            syn::ReturnType::Type(_, ty) if matches!(**ty, Type::ImplTrait(_)) => {
+                return Err(syn::Error::new_spanned(ty, "intercepted methods must name their return type"));
+            }
+            syn::ReturnType::Type(_, ty) => (**ty).clone(),
+        };
+
+        let method_name = method.sig.ident.to_string();
+        let collaborators = collaborators(method);
+        let (original, arg_names) = split_method(method, "intercepted");
+        let original_name = original.sig.ident.clone();
+        originals.push(ImplItem::Fn(original));
+        let component = type_string(&quote!(#self_ty));
+        // Looked up before the call takes `self`, which may be an `Arc<Self>`
+        let resolved: Vec<_> = (0..interceptors.len()).map(|i| quote::format_ident!("interceptor{}", i)).collect();
+        let chain = resolved.iter().rev().map(|interceptor| quote! {
+            let call = match #interceptor {
+                Some(interceptor) => rspring::Proceed::new(async move {
+                    rspring::Interceptor::intercept(&*interceptor, invocation, call).await
+                }),
+                None => call,
+            };
+        });
+        method.block = parse_quote! {{
+            let invocation = rspring::Invocation {
+                component: #component,
+                method: #method_name,
+                args: vec![#({
+                    use rspring::__private::{DescribeDebug as _, DescribeOpaque as _};
+                    (&&rspring::__private::Describe(&#arg_names)).describe()
+                }),*],
+            };
+            let invocation = &invocation;
+            #(let #resolved = #collaborators.get::<#interceptors>();)*
+            let call = rspring::Proceed::new(async move {
+                let value: #output = self.#original_name(#(#arg_names),*).await;
+                let debug = {
+                    use rspring::__private::{DebugFn as _, OpaqueFn as _};
+                    (&&rspring::__private::Describe(&value)).debug_fn()
+                };
+                rspring::ReturnValue::from_parts(value, debug)
+            });
+            #(#chain)*
+            match call.proceed().await.take::<#output>() {
+                Ok(value) => value,
+                Err(value) => panic!(
+                    "an interceptor of {}::{} returned {:?} instead of a {}",
+                    invocation.component, invocation.method, value, std::any::type_name::<#output>(),
+                ),
+            }
+        }};
+        used.extend(interceptors);
+    }
+    items.extend(originals);
+
+    let mut seen = std::collections::HashSet::new();
+    used.retain(|ty| seen.insert(quote!(#ty).to_string()));
+    Ok(used)
+}
+
+// #[async_method] or #[async_method(executor = "reports")] runs a method taking
+// `self: Arc<Self>` or `self: &Arc<Self>` on the named TaskExecutor ("default" if
+// none), returning right away a TaskHandle of its output instead. The task holds a
+// clone of that Arc, the arguments are moved into it. Sync methods run on a
+// blocking thread. Returns whether any method is spawned.
+fn async_methods(items: &mut Vec<ImplItem>) -> syn::Result<bool> {
+    let mut originals = Vec::new();
+    for item in items.iter_mut() {
+        let ImplItem::Fn(method) = item else {
+            continue;
+        };
+        let Some(pos) = method.attrs.iter().position(|attr| attr.path().is_ident("async_method")) else {
+            continue;
+        };
+        let attr = method.attrs.remove(pos);
+        let mut executor = LitStr::new("default", proc_macro2::Span::call_site());
+        if !matches!(attr.meta, syn::Meta::Path(_)) {
+            attr.parse_nested_meta(|meta| {
+                if !meta.path.is_ident("executor") {
+                    return Err(meta.error("expected `executor`"));
+                }
+                executor = meta.value()?.parse()?;
+                Ok(())
+            })?;
+        }
+        let holds_arc = method.sig.receiver().is_some_and(|receiver| {
+            let ty = match &*receiver.ty {
+                Type::Reference(reference) if reference.mutability.is_none() => &*reference.elem,
+                ty => ty,
+            };
+            receiver.colon_token.is_some() && generic_arg(ty, "Arc").is_some_and(|inner| {
+                matches!(inner, Type::Path(path) if path.qself.is_none() && path.path.is_ident("Self"))
+            })
+        });
+        if !holds_arc {
+            return Err(syn::Error::new_spanned(
+                &method.sig,
+                "async methods take `self: Arc<Self>` or `self: &Arc<Self>`, which the task keeps a clone of",
+            ));
+        }
+        let output: Type = match &method.sig.output {
+            syn::ReturnType::Default => parse_quote! { () },
+            syn::ReturnType::Type(_, ty) if matches!(**ty, Type::ImplTrait(_)) => {
+                return Err(syn::Error::new_spanned(ty, "async methods must name their return type"));
+            }
+            syn::ReturnType::Type(_, ty) => (**ty).clone(),
+        };
+        let collaborators = collaborators(method);
+        let (original, arg_names) = split_method(method, "async");
+        let asyncness = method.sig.asyncness.take();
+        let original_name = &original.sig.ident;
+        let submit = match asyncness {
+            Some(_) => quote! { submit(async move { component.#original_name(#(#arg_names),*).await }) },
+            None => quote! { submit_blocking(move || component.#original_name(#(#arg_names),*)) },
+        };
+        method.sig.output = parse_quote! { -> rspring::TaskHandle<#output> };
+        method.block = parse_quote! {{
+            let component = std::sync::Arc::clone(&self);
+            rspring::__private::executor(&#collaborators, #executor).#submit
+        }};
+        originals.push(ImplItem::Fn(original));
+    }
+    let spawned = !originals.is_empty();
+    items.extend(originals);
+    Ok(spawned)
+}
+
+// `self.collaborators` in the wrapper of `method`, so that a component without
+// that field is reported at the method rather than inside the generated code
+fn collaborators(method: &ImplItemFn) -> TokenStream2 {
+    quote::quote_spanned! { method.sig.ident.span()=> self.collaborators }
+}
+
+// Move the body of `method` to a private `__<prefix>_<method>` returned with the
+// argument names, leaving `method` to be given a body that calls it. Arguments
+// bound by a pattern, such as `Path(id)`, are renamed `arg<position>`.
+fn split_method(method: &mut ImplItemFn, prefix: &str) -> (ImplItemFn, Vec<syn::Ident>) {
+    let mut original = method.clone();
+    original.sig.ident = quote::format_ident!("__{}_{}", prefix, method.sig.ident);
+    original.vis = syn::Visibility::Inherited;
+    original.attrs.retain(|attr| attr.path().is_ident("allow"));
+
+    let mut arg_names = Vec::new();
+    for (i, arg) in method.sig.inputs.iter_mut().enumerate() {
+        let FnArg::Typed(pat_type) = arg else {
+            continue;
+        };
+        let arg_name = match &*pat_type.pat {
+            syn::Pat::Ident(pat) if pat.subpat.is_none() => pat.ident.clone(),
+            _ => quote::format_ident!("arg{}", i),
+        };
+        *pat_type.pat = parse_quote! { #arg_name };
+        arg_names.push(arg_name);
+    }
+    (original, arg_names)
+}
+
+// #[retryable(max_attempts = 3, backoff = "exponential", delay = "100ms", retry_on = MyError)]
+// calls an async method returning a Result again when it fails, only for errors
+// caused by one of the `retry_on` types if given. #[circuit_breaker(name = "inventory",
+// failure_rate = 50, wait = "30s", window = 10, fallback = "reserve_fallback")] stops
+// calling it while too many of the latest calls failed, calling the fallback method,
+// which takes the same arguments, instead or when the call fails. The retries happen
+// within the breaker, which counts them as one call. Returns whether any method
+// uses a circuit breaker.
+fn resilience_methods(self_ty: &Type, items: &mut Vec<ImplItem>) -> syn::Result<bool> {
+    let mut originals = Vec::new();
+    let mut breakers = false;
+    for item in items.iter_mut() {
+        let ImplItem::Fn(method) = item else {
+            continue;
+        };
+        let mut retry = None;
+        let mut breaker = None;
+        while let Some(pos) = method.attrs.iter()
+            .position(|attr| attr.path().is_ident("retryable") || attr.path().is_ident("circuit_breaker"))
+        {
+            let attr = method.attrs.remove(pos);
+            let slot = if attr.path().is_ident("retryable") { &mut retry } else { &mut breaker };
+            if slot.is_some() {
+                return Err(syn::Error::new_spanned(&attr, "only one per method"));
+            }
+            *slot = Some(attr);
+        }
+        let Some(attr) = retry.as_ref().or(breaker.as_ref()) else {
+            continue;
+        };
+        if method.sig.asyncness.is_none() || method.sig.receiver().is_none() || !returns_result(&method.sig) {
+            return Err(syn::Error::new_spanned(attr, "only async methods taking self and returning a Result can be retried or guarded by a circuit breaker"));
+        }
+        let method_name = format!("{}::{}", type_string(&quote!(#self_ty)), method.sig.ident);
+        let error_ty = match &method.sig.output {
+            syn::ReturnType::Type(_, ty) => result_error(ty).cloned(),
+            syn::ReturnType::Default => None,
+        };
+
+        let collaborators = collaborators(method);
+        let (original, arg_names) = split_method(method, "guarded");
+        let original_name = original.sig.ident.clone();
+        originals.push(ImplItem::Fn(original));
+
+        let breaker = breaker.as_ref().map(parse_circuit_breaker).transpose()?;
+        let fallback = breaker.as_ref().and_then(|breaker| breaker.fallback.as_ref());
+        // The arguments are needed again for each attempt and for the fallback
+        let call = match (&retry, fallback) {
+            (Some(attr), _) => {
+                let mut max_attempts: u32 = 3;
+                let mut backoff = quote! { rspring::Backoff::Fixed };
+                let mut delay = 100;
+                let mut retry_on = Vec::new();
+                attr.parse_nested_meta(|meta| {
+                    if meta.path.is_ident("max_attempts") {
+                        max_attempts = meta.value()?.parse::<syn::LitInt>()?.base10_parse()?;
+                        if max_attempts == 0 {
+                            return Err(meta.error("max_attempts counts the first call and must be at least 1"));
+                        }
+                    } else if meta.path.is_ident("backoff") {
+                        let value: LitStr = meta.value()?.parse()?;
+                        backoff = match value.value().as_str() {
+                            "fixed" => quote! { rspring::Backoff::Fixed },
+                            "exponential" => quote! { rspring::Backoff::Exponential },
+                            _ => return Err(syn::Error::new(value.span(), "expected backoff \"fixed\" or \"exponential\"")),
+                        };
+                    } else if meta.path.is_ident("delay") {
+                        delay = duration_millis(&meta.value()?.parse()?)?;
+                    } else if meta.path.is_ident("retry_on") {
+                        // retry_on = MyError, or retry_on(A, B)
+                        if meta.input.peek(syn::Token![=]) {
+                            retry_on.push(meta.value()?.parse::<Type>()?);
+                        } else {
+                            let content;
+                            syn::parenthesized!(content in meta.input);
+                            retry_on.extend(content.parse_terminated(Type::parse, syn::Token![,])?);
                         }
+                    } else {
+                        return Err(meta.error("expected `max_attempts`, `backoff`, `delay` or `retry_on`"));
                     }
-                }
-                
-                let deps_array = if deps.is_empty() {
-                    quote! { &[] }
+                    Ok(())
+                })?;
+                let retry_on = if retry_on.is_empty() {
+                    quote! { |_| true }
                 } else {
-                    quote! { &[#(#deps),*] }
+                    let Some(error_ty) = &error_ty else {
+                        return Err(syn::Error::new_spanned(&method.sig.output, "`retry_on` needs the return type written as Result<T, E>"));
+                    };
+                    quote! {
+                        |err: &#error_ty| {
+                            use rspring::__private::{AsBoxedError as _, AsError as _};
+                            let err = (&&rspring::__private::ErrorRef(err)).as_error();
+                            #(rspring::__private::caused_by::<#retry_on>(err))||*
+                        }
+                    }
                 };
-                
-                component_registration = Some(quote! {
-                    rspring::inventory::submit! {
-                        rspring::Component {
-                            name: #name_str,
-                            type_id: std::any::TypeId::of::<#self_ty>(),
-                            dependencies: #deps_array,
-                            build: |container| {
-                                std::sync::Arc::new(#self_ty::new(
-                                    #(#inject_calls),*
-                                ))
+                let arg_refs: Vec<_> = arg_names.iter().map(|arg| quote::format_ident!("{}_ref", arg)).collect();
+                quote! {
+                    {
+                        #(let #arg_refs = &#arg_names;)*
+                        let policy = rspring::RetryPolicy {
+                            max_attempts: #max_attempts,
+                            backoff: #backoff,
+                            delay: std::time::Duration::from_millis(#delay),
+                        };
+                        policy.run(#method_name, move || self.#original_name(#(#arg_refs.clone()),*), #retry_on)
+                    }
+                }
+            }
+            (None, Some(_)) => quote! { self.#original_name(#(#arg_names.clone()),*) },
+            (None, None) => quote! { self.#original_name(#(#arg_names),*) },
+        };
+
+        method.block = match breaker {
+            None => parse_quote! {{ #call.await }},
+            Some(CircuitBreakerArgs { name, failure_rate, wait, window, fallback }) => {
+                breakers = true;
+                let name = name.map_or(method_name, |name| name.value());
+                let rejected = match &fallback {
+                    Some(fallback) => quote! { _ => self.#fallback(#(#arg_names),*).await },
+                    None => quote! {
+                        Ok(value) => value,
+                        Err(rejected) => Err(From::from(rejected)),
+                    },
+                };
+                parse_quote! {{
+                    let Some(breakers) = #collaborators.get::<rspring::CircuitBreakerRegistry>() else {
+                        return #call.await;
+                    };
+                    let breaker = breakers.breaker(#name, rspring::CircuitBreakerConfig {
+                        failure_rate: #failure_rate,
+                        wait: std::time::Duration::from_millis(#wait),
+                        window: #window,
+                    });
+                    match breaker.call(#call).await {
+                        Ok(Ok(value)) => Ok(value),
+                        #rejected
+                    }
+                }}
+            }
+        };
+    }
+
+    items.extend(originals);
+    Ok(breakers)
+}
+
+struct CircuitBreakerArgs {
+    name: Option<LitStr>,
+    failure_rate: u8,
+    wait: u64,
+    window: usize,
+    fallback: Option<syn::Ident>,
+}
+
+fn parse_circuit_breaker(attr: &Attribute) -> syn::Result<CircuitBreakerArgs> {
+    let mut args = CircuitBreakerArgs { name: None, failure_rate: 50, wait: 60_000, window: 10, fallback: None };
+    if let syn::Meta::Path(_) = attr.meta {
+        return Ok(args);
+    }
+    attr.parse_nested_meta(|meta| {
+        if meta.path.is_ident("name") {
+            args.name = Some(meta.value()?.parse()?);
+        } else if meta.path.is_ident("failure_rate") {
+            args.failure_rate = meta.value()?.parse::<syn::LitInt>()?.base10_parse()?;
+            if !(1..=100).contains(&args.failure_rate) {
+                return Err(meta.error("failure_rate is a percentage, from 1 to 100"));
+            }
+        } else if meta.path.is_ident("wait") {
+            args.wait = duration_millis(&meta.value()?.parse()?)?;
+        } else if meta.path.is_ident("window") {
+            args.window = meta.value()?.parse::<syn::LitInt>()?.base10_parse()?;
+            if args.window == 0 {
+                return Err(meta.error("window must be at least 1"));
+            }
+        } else if meta.path.is_ident("fallback") {
+            let fallback: LitStr = meta.value()?.parse()?;
+            args.fallback = Some(fallback.parse()?);
+        } else {
+            return Err(meta.error("expected `name`, `failure_rate`, `wait`, `window` or `fallback`"));
+        }
+        Ok(())
+    })?;
+    Ok(args)
+}
+
+// "250ms", "30s", "5m", "1h" or "1d", read like duration properties but checked
+// here rather than when the method is called
+fn duration_millis(value: &LitStr) -> syn::Result<u64> {
+    rspring::__private::parse_duration(&value.value())
+        .map(|duration| u64::try_from(duration.as_millis()).unwrap_or(u64::MAX))
+        .map_err(|reason| syn::Error::new(value.span(), format!("invalid duration: {}", reason)))
+}
+
+// `E` in `Result<T, E>`
+fn result_error(ty: &Type) -> Option<&Type> {
+    let Type::Path(type_path) = ty else {
+        return None;
+    };
+    let segment = type_path.path.segments.last()?;
+    if segment.ident != "Result" {
+        return None;
+    }
+    let syn::PathArguments::AngleBracketed(args) = &segment.arguments else {
+        return None;
+    };
+    args.args.iter().filter_map(|arg| match arg {
+        syn::GenericArgument::Type(ty) => Some(ty),
+        _ => None,
+    }).nth(1)
+}
+
+// #[cacheable("users")] returns the value cached under the method's arguments,
+// calling the method only on a miss; `key = expr` picks the key from them.
+// #[cache_put("users", key = expr)] always calls it and caches the result, and
+// #[cache_evict("users", key = expr)] or #[cache_evict("users", all_entries)]
+// removes entries once it returns. With a `Result`, only `Ok` values are cached
+// and failures leave the cache untouched. Returns whether any method is cached.
+fn cache_methods(items: &mut Vec<ImplItem>) -> syn::Result<bool> {
+    let mut originals = Vec::new();
+    for item in items.iter_mut() {
+        let ImplItem::Fn(method) = item else {
+            continue;
+        };
+        let mut operations = Vec::new();
+        let mut cacheable = None;
+        let mut i = 0;
+        while i < method.attrs.len() {
+            let attr = &method.attrs[i];
+            let kind = ["cacheable", "cache_put", "cache_evict"].into_iter().find(|kind| attr.path().is_ident(kind));
+            let Some(kind) = kind else {
+                i += 1;
+                continue;
+            };
+            let attr = method.attrs.remove(i);
+            let operation: CacheOperation = attr.parse_args()?;
+            if operation.all_entries && kind != "cache_evict" {
+                return Err(syn::Error::new_spanned(&attr, "`all_entries` is only for #[cache_evict]"));
+            }
+            if kind == "cacheable" {
+                if cacheable.is_some() {
+                    return Err(syn::Error::new_spanned(&attr, "only one #[cacheable] per method"));
+                }
+                cacheable = Some(operation);
+            } else {
+                operations.push((kind, operation));
+            }
+        }
+        if cacheable.is_none() && operations.is_empty() {
+            continue;
+        }
+        if method.sig.receiver().is_none() {
+            return Err(syn::Error::new_spanned(&method.sig, "only methods taking self can be cached"));
+        }
+        let output: Type = match &method.sig.output {
+            syn::ReturnType::Default => parse_quote! { () },
+            syn::ReturnType::Type(_, ty) => (**ty).clone(),
+        };
+        let fallible = returns_result(&method.sig);
+        let awaited = method.sig.asyncness.map(|_| quote! { .await });
+
+        let collaborators = collaborators(method);
+        let (original, arg_names) = split_method(method, "cached");
+        let original_name = &original.sig.ident;
+        let default_key = match arg_names.as_slice() {
+            [arg] => quote! { &#arg },
+            args => quote! { (#(&#args),*) },
+        };
+        let key = |operation: &CacheOperation| match &operation.key {
+            Some(key) => quote! { format!("{:?}", #key) },
+            None => quote! { format!("{:?}", #default_key) },
+        };
+        let call = quote! { self.#original_name(#(#arg_names),*) };
+
+        // Keys are computed first, since the arguments are moved into the call
+        let mut keys = Vec::new();
+        let mut after = Vec::new();
+        for (n, (kind, operation)) in operations.iter().enumerate() {
+            let key_name = quote::format_ident!("key{}", n);
+            let cache = &operation.cache;
+            if !operation.all_entries {
+                let key = key(operation);
+                keys.push(quote! { let #key_name = #key; });
+            }
+            after.push(match (*kind, operation.all_entries, fallible) {
+                ("cache_put", _, true) => quote! {
+                    if let Ok(value) = &value {
+                        caches.cache(#cache).put(&#key_name, value.clone());
+                    }
+                },
+                ("cache_put", _, false) => quote! { caches.cache(#cache).put(&#key_name, value.clone()); },
+                (_, true, _) => quote! { caches.cache(#cache).clear(); },
+                (_, false, _) => quote! { caches.cache(#cache).evict(&#key_name); },
+            });
+        }
+        let succeeded = if fallible { quote! { value.is_ok() } } else { quote! { true } };
+
+        let value = match &cacheable {
+            None => quote! { #call #awaited },
+            Some(operation) => {
+                let cache = &operation.cache;
+                let key = key(operation);
+                keys.push(quote! { let key = #key; });
+                match (awaited.is_some(), fallible) {
+                    (true, true) => quote! { caches.cache(#cache).get_or_try_load(key, #call).await },
+                    (true, false) => quote! { caches.cache(#cache).get_or_load(key, #call).await },
+                    (false, true) => quote! {
+                        match caches.cache(#cache).get(&key) {
+                            Some(value) => Ok(value),
+                            None => #call.inspect(|value| caches.cache(#cache).put(&key, value.clone())),
+                        }
+                    },
+                    (false, false) => quote! {
+                        match caches.cache(#cache).get(&key) {
+                            Some(value) => value,
+                            None => {
+                                let value = #call;
+                                caches.cache(#cache).put(&key, value.clone());
+                                value
                             }
                         }
-                    }
-                });
+                    },
+                }
+            }
+        };
+        method.block = parse_quote! {{
+            let Some(caches) = #collaborators.get::<rspring::CacheManager>() else {
+                return #call #awaited;
+            };
+            #(#keys)*
+            let value: #output = #value;
+            if #succeeded {
+                #(#after)*
+            }
+            value
+        }};
+        originals.push(ImplItem::Fn(original));
+    }
+
+    let cached = !originals.is_empty();
+    items.extend(originals);
+    Ok(cached)
+}
+
+// ("users"), ("users", key = expr) or ("users", all_entries)
+struct CacheOperation {
+    cache: LitStr,
+    key: Option<syn::Expr>,
+    all_entries: bool,
+}
+
+impl Parse for CacheOperation {
+    fn parse(input: syn::parse::ParseStream) -> syn::Result<Self> {
+        let mut operation = CacheOperation { cache: input.parse()?, key: None, all_entries: false };
+        while !input.is_empty() {
+            input.parse::<syn::Token![,]>()?;
+            if input.is_empty() {
+                break;
             }
+            let name: syn::Ident = input.parse()?;
+            if name == "key" {
+                input.parse::<syn::Token![=]>()?;
+                operation.key = Some(input.parse()?);
+            } else if name == "all_entries" {
+                operation.all_entries = true;
+            } else {
+                return Err(syn::Error::new(name.span(), "expected `key = ..` or `all_entries`"));
+            }
+        }
+        if operation.key.is_some() && operation.all_entries {
+            return Err(input.error("`key` and `all_entries` cannot be combined"));
         }
+        Ok(operation)
     }
+}
+
+// #[scheduled(cron = "0 */5 * * * *", zone = "Europe/Paris")], #[scheduled(fixed_rate = "10s")]
+// or #[scheduled(fixed_delay = "1m", initial_delay = "30s")] on `fn job(&self)`, which
+// may be async and return a Result; run by the Scheduler once the application started
+fn scheduled_tasks(self_ty: &Type, items: &mut [ImplItem]) -> syn::Result<Vec<TokenStream2>> {
+    let mut tasks = Vec::new();
+    for item in items {
+        let ImplItem::Fn(method) = item else {
+            continue;
+        };
+        let Some(pos) = method.attrs.iter().position(|attr| attr.path().is_ident("scheduled")) else {
+            continue;
+        };
+        let attr = method.attrs.remove(pos);
+        if method.sig.receiver().is_none() || method.sig.inputs.len() != 1 {
+            return Err(syn::Error::new_spanned(&method.sig, "scheduled methods only take `&self`"));
+        }
+
+        let mut trigger: Option<(&str, LitStr)> = None;
+        let mut zone = None;
+        let mut initial_delay = 0;
+        attr.parse_nested_meta(|meta| {
+            let kind = ["cron", "fixed_rate", "fixed_delay"].into_iter().find(|kind| meta.path.is_ident(kind));
+            if let Some(kind) = kind {
+                if trigger.is_some() {
+                    return Err(meta.error("expected only one of `cron`, `fixed_rate` and `fixed_delay`"));
+                }
+                trigger = Some((kind, meta.value()?.parse()?));
+            } else if meta.path.is_ident("zone") {
+                zone = Some(meta.value()?.parse::<LitStr>()?);
+            } else if meta.path.is_ident("initial_delay") {
+                initial_delay = duration_millis(&meta.value()?.parse()?)?;
+            } else {
+                return Err(meta.error("expected `cron`, `fixed_rate`, `fixed_delay`, `zone` or `initial_delay`"));
+            }
+            Ok(())
+        })?;
+        let trigger = match (trigger, zone) {
+            (None, _) => return Err(syn::Error::new_spanned(attr, "expected `cron`, `fixed_rate` or `fixed_delay`")),
+            // The expression and the zone are checked when the Scheduler starts
+            (Some(("cron", expression)), zone) => {
+                let zone = match zone {
+                    Some(zone) => quote! { Some(#zone) },
+                    None => quote! { None },
+                };
+                quote! { rspring::Trigger::Cron { expression: #expression, zone: #zone } }
+            }
+            (Some(_), Some(zone)) => return Err(syn::Error::new_spanned(zone, "`zone` only applies to `cron`")),
+            (Some(("fixed_rate", period)), None) => {
+                let millis = duration_millis(&period)?;
+                quote! { rspring::Trigger::FixedRate(std::time::Duration::from_millis(#millis)) }
+            }
+            (Some((_, delay)), None) => {
+                let millis = duration_millis(&delay)?;
+                quote! { rspring::Trigger::FixedDelay(std::time::Duration::from_millis(#millis)) }
+            }
+        };
+
+        let method_name = &method.sig.ident;
+        let name = format!("{}::{}", type_string(&quote!(#self_ty)), method_name);
+        let awaited = method.sig.asyncness.map(|_| quote! { .await });
+        let fallible = returns_result(&method.sig).then(|| quote! { ? });
+        tasks.push(quote! {
+            rspring::inventory::submit! {
+                rspring::ScheduledTask {
+                    component: std::any::TypeId::of::<#self_ty>(),
+                    name: #name,
+                    trigger: #trigger,
+                    initial_delay: std::time::Duration::from_millis(#initial_delay),
+                    run: |container| std::boxed::Box::pin(async move {
+                        let component = container.get::<#self_ty>();
+                        component.#method_name()#awaited #fallible;
+                        Ok::<(), rspring::BuildError>(())
+                    }),
+                }
+            }
+        });
+    }
+    Ok(tasks)
+}
+
+// #[event_listener], #[event_listener(async, order = 1, condition = |e| e.total > 100)]
+// on `fn on_event(&self, event: &Event)`, which may be async and return a Result
+fn event_listeners(self_ty: &Type, items: &mut [ImplItem]) -> syn::Result<Vec<TokenStream2>> {
+    let mut listeners = Vec::new();
+    for item in items {
+        let ImplItem::Fn(method) = item else {
+            continue;
+        };
+        let Some(pos) = method.attrs.iter().position(|attr| attr.path().is_ident("event_listener")) else {
+            continue;
+        };
+        let attr = method.attrs.remove(pos);
+
+        let mut background = false;
+        let mut order = quote! { i32::MAX };
+        let mut condition = None;
+        if let syn::Meta::List(_) = attr.meta {
+            attr.parse_nested_meta(|meta| {
+                if meta.path.is_ident("async") {
+                    background = true;
+                } else if meta.path.is_ident("order") {
+                    let value: syn::LitInt = meta.value()?.parse()?;
+                    order = quote! { #value };
+                } else if meta.path.is_ident("condition") {
+                    condition = Some(meta.value()?.parse::<syn::Expr>()?);
+                } else {
+                    return Err(meta.error("expected `async`, `order` or `condition`"));
+                }
+                Ok(())
+            })?;
+        }
+
+        let event_ty = match (method.sig.receiver(), method.sig.inputs.iter().nth(1), method.sig.inputs.len()) {
+            (Some(_), Some(FnArg::Typed(arg)), 2) => match &*arg.ty {
+                Type::Reference(reference) if reference.mutability.is_none() => reference.elem.clone(),
+                _ => return Err(syn::Error::new_spanned(&arg.ty, "the event must be taken by reference, e.g. `event: &OrderPlaced`")),
+            },
+            _ => return Err(syn::Error::new_spanned(&method.sig, "event listeners take `&self` and the event, e.g. `fn on_order_placed(&self, event: &OrderPlaced)`")),
+        };
+
+        let method_name = &method.sig.ident;
+        let name = format!("{}::{}", type_string(&quote!(#self_ty)), method_name);
+        let awaited = method.sig.asyncness.map(|_| quote! { .await });
+        let fallible = returns_result(&method.sig).then(|| quote! { ? });
+        let condition = match condition {
+            Some(condition) => quote! {
+                Some(|event| {
+                    let condition: fn(&#event_ty) -> bool = #condition;
+                    condition(event.downcast_ref::<#event_ty>().unwrap())
+                })
+            },
+            None => quote! { None },
+        };
+
+        listeners.push(quote! {
+            rspring::inventory::submit! {
+                rspring::EventListener {
+                    component: std::any::TypeId::of::<#self_ty>(),
+                    name: #name,
+                    event: std::any::TypeId::of::<#event_ty>(),
+                    order: #order,
+                    background: #background,
+                    condition: #condition,
+                    handle: |container, event| std::boxed::Box::pin(async move {
+                        let component = container.get::<#self_ty>();
+                        let event = event.downcast_ref::<#event_ty>().unwrap();
+                        component.#method_name(event)#awaited #fallible;
+                        Ok::<(), rspring::BuildError>(())
+                    }),
+                }
+            }
+        });
+    }
+    Ok(listeners)
+}
+
+// How a constructor argument or struct field is filled from the container
+struct Injection {
+    // Entry in `Component::dependencies`, none for a #[value]
+    dependency: Option<TokenStream2>,
+    // Expression evaluated inside `build`
+    value: TokenStream2,
+}
+
+// `Arc<T>` is injected from the container, honouring a #[qualifier("name")]
+// attribute, which is removed. `Vec<Arc<T>>` receives every component of type `T`,
+// `Option<Arc<T>>` is `None` when nothing is registered and `Provider<T>` looks `T`
+// up on use. #[value("${key:default}")] parses a property with `FromStr`, and
+// `Collaborators` holds what the wrapped methods of `component` call.
+// Other types are not injected.
+fn injection(ty: &Type, attrs: &mut Vec<Attribute>, component: &TokenStream2) -> syn::Result<Option<Injection>> {
+    if let Some(pos) = attrs.iter().position(|attr| attr.path().is_ident("value")) {
+        let attr = attrs.remove(pos);
+        let (key, default) = parse_placeholder(&attr.parse_args()?)?;
+        let default = match default {
+            Some(default) => quote! { Some(#default) },
+            None => quote! { None },
+        };
+        return Ok(Some(Injection {
+            dependency: None,
+            value: quote! { container.environment().value::<#ty>(#key, #default)? },
+        }));
+    }
+
+    if is_collaborators(ty) {
+        // Its dependencies are those of the component's wrapped methods
+        return Ok(Some(Injection {
+            dependency: None,
+            value: quote! { rspring::Collaborators::resolve::<#component>(container)? },
+        }));
+    }
+
+    let mut qualifier = None;
+    for attr in attrs.iter().filter(|attr| attr.path().is_ident("qualifier")) {
+        qualifier = Some(attr.parse_args::<LitStr>()?);
+    }
+    attrs.retain(|attr| !attr.path().is_ident("qualifier"));
+
+    if let Some(inner_ty) = generic_arg(ty, "Vec").and_then(|ty| generic_arg(ty, "Arc")) {
+        if let Some(name) = qualifier {
+            return Err(syn::Error::new(name.span(), "#[qualifier] cannot be used on a collection"));
+        }
+        return Ok(Some(Injection {
+            dependency: Some(quote! { rspring::Dependency::all::<#inner_ty>() }),
+            value: quote! { container.get_all::<#inner_ty>() },
+        }));
+    }
+
+    let named = qualifier.as_ref().map(|name| quote! { .named(#name) });
+
+    if let Some(inner_ty) = generic_arg(ty, "Provider") {
+        return Ok(Some(Injection {
+            dependency: Some(quote! { rspring::Dependency::provider::<#inner_ty>()#named }),
+            value: quote! { container.provider::<#inner_ty>()#named },
+        }));
+    }
+
+    if let Some(inner_ty) = generic_arg(ty, "Option").and_then(|ty| generic_arg(ty, "Arc")) {
+        let value = match &qualifier {
+            Some(name) => quote! { container.try_get_named::<#inner_ty>(#name) },
+            None => quote! { container.try_get::<#inner_ty>() },
+        };
+        return Ok(Some(Injection {
+            dependency: Some(quote! { rspring::Dependency::optional::<#inner_ty>()#named }),
+            value,
+        }));
+    }
+
+    let Some(inner_ty) = generic_arg(ty, "Arc") else {
+        return Ok(None);
+    };
+
+    Ok(Some(match &qualifier {
+        Some(name) => Injection {
+            dependency: Some(quote! { rspring::Dependency::of::<#inner_ty>()#named }),
+            value: quote! { container.get_named::<#inner_ty>(#name) },
+        },
+        None => Injection {
+            dependency: Some(quote! { rspring::Dependency::of::<#inner_ty>() }),
+            value: quote! { container.get::<#inner_ty>() },
+        },
+    }))
+}
+
+fn is_collaborators(ty: &Type) -> bool {
+    matches!(ty, Type::Path(path) if path.path.segments.last().is_some_and(|s| s.ident == "Collaborators"))
+}
+
+// "${key}" or "${key:default}"
+fn parse_placeholder(expr: &LitStr) -> syn::Result<(String, Option<String>)> {
+    let text = expr.value();
+    let Some(placeholder) = text.strip_prefix("${").and_then(|text| text.strip_suffix('}')) else {
+        return Err(syn::Error::new(expr.span(), "expected \"${property}\" or \"${property:default}\""));
+    };
+    let (key, default) = match placeholder.split_once(':') {
+        Some((key, default)) => (key, Some(default.to_string())),
+        None => (placeholder, None),
+    };
+    if key.trim().is_empty() {
+        return Err(syn::Error::new(expr.span(), "missing property name"));
+    }
+    Ok((key.trim().to_string(), default))
+}
+
+// The type argument of `Wrapper<T>`, e.g. the `T` in `Arc<T>`
+fn generic_arg<'a>(ty: &'a Type, wrapper: &str) -> Option<&'a Type> {
+    let Type::Path(type_path) = ty else {
+        return None;
+    };
+    let segment = type_path.path.segments.last()?;
+    if segment.ident != wrapper {
+        return None;
+    }
+    let syn::PathArguments::AngleBracketed(args) = &segment.arguments else {
+        return None;
+    };
+    match args.args.first()? {
+        syn::GenericArgument::Type(inner_ty) => Some(inner_ty),
+        _ => None,
+    }
+}
+
+// Registers `self_ty` as a Component built through its `new` method,
+// injecting its arguments from the container
+fn constructor_registration(self_ty: &Type, method: &mut ImplItemFn, args: &ComponentArgs) -> syn::Result<TokenStream2> {
+    let name_str = args.component_name(&quote!(#self_ty));
+    let takes_collaborators = method.sig.inputs.iter().any(|arg| matches!(arg, FnArg::Typed(arg) if is_collaborators(&arg.ty)));
+    if let (Some(span), false) = (args.wrapped_at, takes_collaborators) {
+        return Err(syn::Error::new(span, format!(
+            "{} has wrapped methods, so `new` must take a `collaborators: Collaborators` parameter, kept in a field named `collaborators`",
+            type_string(&quote!(#self_ty)),
+        )));
+    }
+    factory_registration(self_ty, &name_str, quote! { <#self_ty>::new }, method, args)
+}
+
+// Registers `product` as a Component named `name_str`, built by calling `factory`
+// with the arguments of `method` injected from the container
+fn factory_registration(
+    product: &Type,
+    name_str: &str,
+    factory: TokenStream2,
+    method: &mut ImplItemFn,
+    args: &ComponentArgs,
+) -> syn::Result<TokenStream2> {
+    // Extract dependencies from the factory arguments
+    let mut deps = Vec::new();
+    let mut inject_calls = Vec::new();
     
-    if let Some(reg) = component_registration {
-        generated_items.push(reg);
+    for arg in &mut method.sig.inputs {
+        if let FnArg::Typed(pat_type) = arg {
+            let Some(injection) = injection(&pat_type.ty, &mut pat_type.attrs, &quote!(#product))? else {
+                return Err(syn::Error::new_spanned(
+                    &pat_type.ty,
+                    "cannot inject this parameter: expected Arc<T>, Vec<Arc<T>>, Option<Arc<T>>, Provider<T>, Collaborators or #[value(\"${property}\")]",
+                ));
+            };
+            deps.extend(injection.dependency);
+            inject_calls.push(injection.value);
+        }
     }
+    // Resolved into `Collaborators`, built first like the other dependencies
+    deps.extend(args.wrapped_dependencies.iter().map(|ty| quote! { rspring::Dependency::of::<#ty>() }));
     
+    let deps_array = if deps.is_empty() {
+        quote! { &[] }
+    } else {
+        quote! { &[#(#deps),*] }
+    };
+    let scope = &args.scope;
+    let primary = args.primary;
+    let order = args.order();
+    let lazy = args.lazy;
+    let conditions = &args.conditions;
+    let tags = &args.tags;
+    let provides = args.provided_types(&quote!(#product));
+
+    let (post_construct, pre_destroy) = args.hooks();
+
+    // `async fn new` is awaited and `-> Result<Self, E>` aborts startup on `Err`
+    let awaited = method.sig.asyncness.map(|_| quote! { .await });
+    let fallible = returns_result(&method.sig).then(|| quote! { ? });
+    
+    Ok(quote! {
+        rspring::inventory::submit! {
+            rspring::Component {
+                name: #name_str,
+                type_id: std::any::TypeId::of::<#product>(),
+                type_name: std::any::type_name::<#product>,
+                module_path: module_path!(),
+                tags: &[#(#tags),*],
+                scope: #scope,
+                primary: #primary,
+                order: #order,
+                lazy: #lazy,
+                conditions: &[#(#conditions),*],
+                provides: #provides,
+                dependencies: #deps_array,
+                build: |container| std::boxed::Box::pin(async move {
+                    let instance = #factory(
+                        #(#inject_calls),*
+                    )#awaited #fallible;
+                    Ok::<_, rspring::BuildError>(std::sync::Arc::new(std::sync::Arc::new(instance)) as rspring::Instance)
+                }),
+                post_construct: #post_construct,
+                pre_destroy: #pre_destroy,
+            }
+        }
+    })
+}
+
+// Every #[bean] function of a #[configuration] impl registers a Component for
+// its return type, named after the function, with its parameters as dependencies
+#[proc_macro_attribute]
+pub fn configuration(_attr: TokenStream, item: TokenStream) -> TokenStream {
+    let mut input = parse_macro_input!(item as ItemImpl);
+    let config_ty = &input.self_ty;
+    let mut generated_items = Vec::new();
+
+    for item in &mut input.items {
+        let ImplItem::Fn(method) = item else {
+            continue;
+        };
+        let Some(pos) = method.attrs.iter().position(|attr| attr.path().is_ident("bean")) else {
+            continue;
+        };
+        let attr = method.attrs.remove(pos);
+        match bean_registration(config_ty, &attr, method) {
+            Ok(registration) => generated_items.push(registration),
+            Err(err) => return err.to_compile_error().into(),
+        }
+    }
+
     quote! {
         #input
         #(#generated_items)*
     }.into()
 }
 
-fn controller_impl_macro(mut input: ItemImpl) -> TokenStream {
+// #[bean] takes the same arguments and markers as #[service], except that
+// the component is named after the function unless `name` is given
+fn bean_registration(config_ty: &Type, attr: &Attribute, method: &mut ImplItemFn) -> syn::Result<TokenStream2> {
+    let mut args = ComponentArgs::default();
+    if let syn::Meta::List(_) = attr.meta {
+        attr.parse_nested_meta(|meta| args.parse(meta))?;
+    }
+    args.take_markers(&mut method.attrs)?;
+
+    if let Some(receiver) = method.sig.receiver() {
+        return Err(syn::Error::new_spanned(receiver, "#[bean] functions cannot take self, inject what they need as parameters"));
+    }
+    let syn::ReturnType::Type(_, output) = &method.sig.output else {
+        return Err(syn::Error::new_spanned(&method.sig, "#[bean] functions must return the component they create"));
+    };
+    // `-> Result<T, E>` registers `T`
+    let product = generic_arg(output, "Result").unwrap_or(output).clone();
+
+    let fn_name = &method.sig.ident;
+    let name_str = args.component_name(&quote!(#fn_name));
+    factory_registration(&product, &name_str, quote! { <#config_ty>::#fn_name }, method, &args)
+}
+
+// ("/api/orders/", "/{id}") -> "/api/orders/{id}", ("/api/orders", "/") -> "/api/orders"
+fn join_paths(base_path: &str, path: &str) -> String {
+    let base_path = base_path.trim_matches('/');
+    let path = path.trim_start_matches('/');
+    match (base_path.is_empty(), path.is_empty()) {
+        (true, _) => format!("/{}", path),
+        (false, true) => format!("/{}", base_path),
+        (false, false) => format!("/{}/{}", base_path, path),
+    }
+}
+
+fn returns_result(sig: &syn::Signature) -> bool {
+    matches!(&sig.output, syn::ReturnType::Type(_, ty) if generic_arg(ty, "Result").is_some())
+}
+
+fn controller_impl_macro(mut input: ItemImpl, mut args: ComponentArgs) -> TokenStream {
+    if let Some(instance) = args.instances.first() {
+        return syn::Error::new_spanned(instance, "`instances` is only supported by #[service]")
+            .to_compile_error()
+            .into();
+    }
+    if let Err(err) = args.take_markers(&mut input.attrs)
+        .and_then(|_| args.take_lifecycle_hooks(&input.self_ty, &mut input.items))
+        .and_then(|_| wrap_methods(&mut input, &mut args))
+    {
+        return err.to_compile_error().into();
+    }
     let self_ty = &input.self_ty;
-    let mut generated_items = Vec::new();
+    let mut generated_items = vec![wrapped_methods(self_ty, &args.wrapped_dependencies)];
     
     // 1. Check for a `new` method to generate Component registration (Constructor Injection)
-    let mut component_registration = None;
-    
-    for item in &input.items {
+    for item in &mut input.items {
         if let ImplItem::Fn(method) = item {
             if method.sig.ident == "new" {
-                let name_str = quote!(#self_ty).to_string();
-                
-                // Extract dependencies from `new` arguments
-                let mut deps = Vec::new();
-                let mut inject_calls = Vec::new();
-                
-                for arg in &method.sig.inputs {
-                    if let FnArg::Typed(pat_type) = arg {
-                        if let Type::Path(type_path) = &*pat_type.ty {
-                            // Check if it's Arc<SomeType>
-                            if let Some(segment) = type_path.path.segments.last() {
-                                if segment.ident == "Arc" {
-                                    if let syn::PathArguments::AngleBracketed(args) = &segment.arguments {
-                                        if let Some(syn::GenericArgument::Type(inner_ty)) = args.args.first() {
-                                            deps.push(quote! { std::any::TypeId::of::<#inner_ty>() });
-                                            inject_calls.push(quote! { container.get::<#inner_ty>() });
-                                        }
-                                    }
-                                }
-                            }
-                        }
-                    }
+                match constructor_registration(self_ty, method, &args) {
+                    Ok(registration) => generated_items.push(registration),
+                    Err(err) => return err.to_compile_error().into(),
                 }
-                
-                let deps_array = if deps.is_empty() {
-                    quote! { &[] }
-                } else {
-                    quote! { &[#(#deps),*] }
-                };
-                
-                component_registration = Some(quote! {
-                    rspring::inventory::submit! {
-                        rspring::Component {
-                            name: #name_str,
-                            type_id: std::any::TypeId::of::<#self_ty>(),
-                            dependencies: #deps_array,
-                            build: |container| {
-                                std::sync::Arc::new(#self_ty::new(
-                                    #(#inject_calls),*
-                                ))
-                            }
-                        }
-                    }
-                });
             }
         }
     }
-    
-    if let Some(reg) = component_registration {
-        generated_items.push(reg);
+
+    match event_listeners(self_ty, &mut input.items) {
+        Ok(listeners) => generated_items.extend(listeners),
+        Err(err) => return err.to_compile_error().into(),
+    }
+    match scheduled_tasks(self_ty, &mut input.items) {
+        Ok(tasks) => generated_items.extend(tasks),
+        Err(err) => return err.to_compile_error().into(),
     }
 
     // 2. Process Routes
@@ -256,6 +1805,10 @@ fn controller_impl_macro(mut input: ItemImpl) -> TokenStream {
                 
                 let method_name = &method.sig.ident;
                 let path_lit: LitStr = attr.parse_args().expect("Invalid route path");
+                let path_lit = match &args.base_path {
+                    Some(base_path) => LitStr::new(&join_paths(&base_path.value(), &path_lit.value()), path_lit.span()),
+                    None => path_lit,
+                };
                 
                 let (http_method, axum_method) = if attr.path().is_ident("get") {
                     (quote! { rspring::Method::GET }, quote! { rspring::axum::routing::get })
@@ -267,7 +1820,7 @@ fn controller_impl_macro(mut input: ItemImpl) -> TokenStream {
                     (quote! { rspring::Method::DELETE }, quote! { rspring::axum::routing::delete })
                 };
 
-                let struct_name = if let Type::Path(type_path) = &*self_ty {
+                let struct_name = if let Type::Path(type_path) = &**self_ty {
                     type_path.path.segments.last().unwrap().ident.to_string()
                 } else {
                     "controller".to_string()
@@ -295,14 +1848,19 @@ fn controller_impl_macro(mut input: ItemImpl) -> TokenStream {
                         rspring::axum::Extension(container): rspring::axum::Extension<std::sync::Arc<rspring::ServiceContainer>>,
                         #(#wrapper_args),*
                     ) #output {
-                        let controller = container.get::<#self_ty>();
-                        controller.#method_name(#(#call_args),*).await
+                        // Request-scoped dependencies are resolved fresh for every request
+                        let container = container.request_scope();
+                        container.clone().scoped(async move {
+                            let controller = container.get::<#self_ty>();
+                            controller.#method_name(#(#call_args),*).await
+                        }).await
                     }
                     
                     rspring::inventory::submit! {
                         rspring::Route {
                             path: #path_lit,
                             method: #http_method,
+                            controller: Some(std::any::TypeId::of::<#self_ty>()),
                             setup: |router| {
                                 router.route(#path_lit, #axum_method(#wrapper_name))
                             }
@@ -331,6 +1889,7 @@ pub fn get(attr: TokenStream, item: TokenStream) -> TokenStream {
             rspring::Route {
                 path: #path,
                 method: rspring::Method::GET,
+                controller: None,
                 setup: |router| {
                     router.route(#path, axum::routing::get(#name))
                 }
@@ -352,6 +1911,7 @@ pub fn delete(attr: TokenStream, item: TokenStream) -> TokenStream {
             rspring::Route {
                 path: #path,
                 method: rspring::Method::DELETE,
+                controller: None,
                 setup: |router| {
                     router.route(#path, axum::routing::delete(#name))
                 }
@@ -374,6 +1934,7 @@ pub fn post(attr: TokenStream, item: TokenStream) -> TokenStream {
             rspring::Route {
                 path: #path,
                 method: rspring::Method::POST,
+                controller: None,
                 setup: |router| {
                     router.route(#path, axum::routing::post(#name))
                 }
@@ -394,6 +1955,7 @@ pub fn put(attr: TokenStream, item: TokenStream) -> TokenStream {
             rspring::Route {
                 path: #path,
                 method: rspring::Method::PUT,
+                controller: None,
                 setup: |router| {
                     router.route(#path, axum::routing::put(#name))
                 }
@@ -426,4 +1988,35 @@ pub fn main(_attr: TokenStream, item: TokenStream) -> TokenStream {
             app.run().await;
         }
     }.into()
-}
\ No newline at end of file
+}
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn join_paths_puts_one_slash_between_the_parts() {
+        assert_eq!(join_paths("/api/orders/", "/{id}"), "/api/orders/{id}");
+        assert_eq!(join_paths("/api/orders", "{id}"), "/api/orders/{id}");
+    }
+
+    #[test]
+    fn join_paths_drops_a_root_route_or_base_path() {
+        assert_eq!(join_paths("/api/orders", "/"), "/api/orders");
+        assert_eq!(join_paths("/", "/x"), "/x");
+        assert_eq!(join_paths("", "/x"), "/x");
+        assert_eq!(join_paths("", "/"), "/");
+    }
+
+    #[test]
+    fn placeholders_name_a_property_and_maybe_a_default() {
+        let parse = |text: &str| parse_placeholder(&LitStr::new(text, proc_macro2::Span::call_site())).map_err(|err| err.to_string());
+
+        assert_eq!(parse("${server.port}"), Ok(("server.port".to_string(), None)));
+        assert_eq!(parse("${ server.port :3000}"), Ok(("server.port".to_string(), Some("3000".to_string()))));
+        // Only the first colon ends the key
+        assert_eq!(parse("${app.url:http://localhost}"), Ok(("app.url".to_string(), Some("http://localhost".to_string()))));
+        assert_eq!(parse("${app.name:}"), Ok(("app.name".to_string(), Some(String::new()))));
+        assert_eq!(parse("${:3000}"), Err("missing property name".to_string()));
+        assert_eq!(parse("server.port"), Err("expected \"${property}\" or \"${property:default}\"".to_string()));
+    }
+}